@@ -0,0 +1,14 @@
+#![no_main]
+
+use dxvk_cache_tool::{bundle::BundleReader, dxvk::*};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(reader) = BundleReader::new(data, &ReadOptions::default()) {
+        for cache in reader {
+            if cache.is_err() {
+                break;
+            }
+        }
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use dxvk_cache_tool::dxvk::*;
+use libfuzzer_sys::fuzz_target;
+
+// The first byte splits the rest of the input into a cache and a second cache appended to it
+fuzz_target!(|data: &[u8]| {
+    let (split, data) = match data.split_first() {
+        Some((&split, data)) => (split as usize, data),
+        None => return,
+    };
+    let (first, second) = data.split_at(split.min(data.len()));
+    let options = ReadOptions::default();
+    if let Ok(mut cache) = DxvkStateCache::from_reader_with(first, &options) {
+        let _ = cache.append_from(second, &options);
+        let mut out = Vec::new();
+        let _ = cache.write_to(&mut out);
+    }
+});
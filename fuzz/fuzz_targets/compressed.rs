@@ -0,0 +1,11 @@
+#![no_main]
+
+use dxvk_cache_tool::{compress, dxvk::*};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let options = ReadOptions::default();
+    if let Ok(reader) = compress::decompress(data) {
+        let _ = DxvkStateCache::from_reader_with(reader, &options);
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use std::num::NonZeroU32;
+use dxvk_cache_tool::dxvk::*;
+use libfuzzer_sys::fuzz_target;
+
+// The first two bytes pick the legacy entry size, which is otherwise fixed by the cache header
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let entry_size = u16::from_le_bytes([data[0], data[1]]) as u32;
+    let header = DxvkStateCacheHeader::new(NonZeroU32::new(LEGACY_VERSION).unwrap(), entry_size);
+    let mut reader = EntryReader::new(&data[2..], header, ReadLimits::default());
+    while let Ok(Some(_)) | Err(EntryError::HashMismatch) = reader.next_entry() {}
+});
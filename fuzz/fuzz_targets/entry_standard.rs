@@ -0,0 +1,11 @@
+#![no_main]
+
+use std::num::NonZeroU32;
+use dxvk_cache_tool::dxvk::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let header = DxvkStateCacheHeader::new(NonZeroU32::new(LEGACY_VERSION + 1).unwrap(), 0);
+    let mut reader = EntryReader::new(data, header, ReadLimits::default());
+    while let Ok(Some(_)) | Err(EntryError::HashMismatch) = reader.next_entry() {}
+});
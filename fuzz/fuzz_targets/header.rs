@@ -0,0 +1,18 @@
+#![no_main]
+
+use dxvk_cache_tool::{
+    dxvk::*,
+    read::FromReader,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = DxvkStateCacheHeader::from_reader(data);
+    for &endian in &[Endian::Little, Endian::Big] {
+        let options = ReadOptions {
+            endian: Some(endian),
+            ..Default::default()
+        };
+        let _ = DxvkStateCacheHeader::from_reader_with(data, &options);
+    }
+});
@@ -0,0 +1,109 @@
+use std::{
+    collections::HashSet,
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        self,
+        BufReader,
+        BufWriter,
+    },
+    path::{Path, PathBuf},
+};
+use crate::{
+    compress,
+    dxvk::*,
+    error::Error,
+};
+
+/// An existing state cache that new entries get appended to in place, the same way DXVK itself
+/// grows a cache. The bytes already in the file are never rewritten.
+pub struct AppendTarget {
+    path:   PathBuf,
+    file:   File,
+    header: DxvkStateCacheHeader,
+    hashes: HashSet<Sha1Hash>,
+}
+
+impl AppendTarget {
+    /// Opens `path` for appending, returning `None` if it doesn't exist yet.
+    ///
+    /// The whole file is scanned up front. If it contains invalid entries or trailing bytes
+    /// that don't make up a complete entry, appending to it would leave the new entries
+    /// unreachable, so the target is rejected instead. So is a big-endian target, as entries are
+    /// always written little-endian.
+    pub fn open<P: AsRef<Path>>(path: P, options: &ReadOptions) -> Result<Option<Self>, Error> {
+        let path = path.as_ref();
+        let file = match fs::OpenOptions::new().read(true).append(true).open(path) {
+            Ok(f) => f,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let len = file.metadata()?.len();
+        let mut reader = BufReader::new(&file);
+        if compress::peek(&mut reader)?.is_some() {
+            return Err(Error::AppendCompressed(path.into()));
+        }
+        let header = DxvkStateCacheHeader::from_reader_with(&mut reader, options)
+            .map_err(|e| ReadError::from(e).in_file(path))?;
+        if header.endian != Endian::Little {
+            return Err(Error::AppendBigEndian(path.into()));
+        }
+        let mut reader = EntryReader::new(reader, header, options.limits);
+        let mut hashes = HashSet::new();
+        let mut end = DxvkStateCacheHeader::SIZE as u64;
+        loop {
+            match reader.next_entry() {
+                Ok(Some(e)) => {
+                    end += e.size_on_disk() as u64;
+                    hashes.insert(e.hash);
+                },
+                Ok(None) => break,
+                Err(EntryError::HashMismatch | EntryError::Truncated) => {
+                    return Err(Error::AppendTargetInvalid(path.into()));
+                },
+                Err(e) => return Err(ReadError::ReadEntry(reader.position(), e).in_file(path).into()),
+            }
+        }
+        if end != len {
+            return Err(Error::AppendTargetInvalid(path.into()));
+        }
+        Ok(Some(AppendTarget {
+            path: path.into(),
+            file: file,
+            header: header,
+            hashes: hashes,
+        }))
+    }
+
+    pub fn check_header(&self, header: &DxvkStateCacheHeader) -> Result<(), Error> {
+        if !self.header.is_compatible(header) {
+            return Err(Error::AppendHeaderMismatch {
+                target: self.header,
+                found: *header,
+            });
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, entry: &DxvkStateCacheEntry) -> bool {
+        self.hashes.contains(&entry.hash)
+    }
+
+    #[inline(always)]
+    pub fn edition(&self) -> DxvkStateCacheEdition {
+        self.header.edition()
+    }
+
+    #[inline(always)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Consumes the target, returning a writer positioned at the end of the existing file
+    pub fn into_writer(self) -> BufWriter<File> {
+        BufWriter::new(self.file)
+    }
+}
@@ -0,0 +1,601 @@
+//! Interactive terminal browser for the entries of a state cache
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Row, Table, TableState},
+    DefaultTerminal,
+    Frame,
+};
+use crate::{
+    compress::{self, Compression, Encoder},
+    dxvk::{
+        DxvkStateCache,
+        DxvkStateCacheHeader,
+        EntryWrapper,
+        HashPrefix,
+        ReadError,
+        ReadOptions,
+        STAGE_NAMES,
+    },
+    show::{self, LocatedEntry},
+    store,
+};
+
+const HELP: &str = "q quit  j/k move  o sort  f filter  / search  n next  e export  d remove  w write  J/K scroll";
+
+/// Order the entries are listed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// The order they're stored in
+    Position,
+    Hash,
+    /// Largest first
+    Size,
+    Stages,
+}
+
+impl SortKey {
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::Position => SortKey::Hash,
+            SortKey::Hash => SortKey::Size,
+            SortKey::Size => SortKey::Stages,
+            SortKey::Stages => SortKey::Position,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Position => "position",
+            SortKey::Hash => "hash",
+            SortKey::Size => "size",
+            SortKey::Stages => "stages",
+        }
+    }
+}
+
+/// What to do with an entry once the marks are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Export,
+    Remove,
+}
+
+/// Which entries a filter keeps, or a search stops at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Entries using at least these stages, given by name like `VS|FS`
+    Stages(u8),
+    /// Entries whose hash starts with these digits, or whose data contains the bytes they spell
+    /// out, such as the hash of a shader
+    Hex(HashPrefix, Vec<u8>),
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stages = s.split(['|', ','])
+            .map(|name| STAGE_NAMES.iter().position(|n| n.eq_ignore_ascii_case(name.trim())))
+            .try_fold(0u8, |mask, bit| bit.map(|bit| mask | 1 << bit));
+        if let Some(mask) = stages {
+            return Ok(Query::Stages(mask));
+        }
+        let prefix = s.parse::<HashPrefix>()
+            .map_err(|_| format!("invalid query \"{}\", expected stage names or hexadecimal digits", s))?;
+        let bytes = if s.len().is_multiple_of(2) {
+            (0..s.len()).step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Ok(Query::Hex(prefix, bytes))
+    }
+}
+
+impl Query {
+    pub fn matches(&self, entry: &LocatedEntry) -> bool {
+        let e = &entry.entry;
+        match self {
+            Query::Stages(mask) => e.header.is_some_and(|h| h.stage_mask & mask == *mask),
+            Query::Hex(prefix, bytes) => {
+                prefix.matches(&e.hash)
+                    || (!bytes.is_empty() && e.data.windows(bytes.len()).any(|w| w == &bytes[..]))
+            },
+        }
+    }
+}
+
+/// The entries of a cache along with how they're sorted, filtered and marked, independently of
+/// the terminal they're shown on
+#[derive(Debug)]
+pub struct Browser {
+    header:   DxvkStateCacheHeader,
+    entries:  Vec<LocatedEntry>,
+    marks:    Vec<Option<Mark>>,
+    /// Indices of the listed entries, in the order they're listed
+    view:     Vec<usize>,
+    sort:     SortKey,
+    filter:   Option<Query>,
+    /// Index into `view`
+    selected: usize,
+}
+
+impl Browser {
+    pub fn new(header: DxvkStateCacheHeader, entries: Vec<LocatedEntry>) -> Self {
+        let mut browser = Browser {
+            header: header,
+            marks: vec![None; entries.len()],
+            entries: entries,
+            view: Vec::new(),
+            sort: SortKey::Position,
+            filter: None,
+            selected: 0,
+        };
+        browser.update_view();
+        browser
+    }
+
+    #[inline]
+    pub fn header(&self) -> &DxvkStateCacheHeader {
+        &self.header
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &[LocatedEntry] {
+        &self.entries
+    }
+
+    /// Indices of the listed entries, in the order they're listed
+    #[inline]
+    pub fn view(&self) -> &[usize] {
+        &self.view
+    }
+
+    #[inline]
+    pub fn mark(&self, index: usize) -> Option<Mark> {
+        self.marks[index]
+    }
+
+    #[inline]
+    pub fn sort(&self) -> SortKey {
+        self.sort
+    }
+
+    #[inline]
+    pub fn filter(&self) -> Option<&Query> {
+        self.filter.as_ref()
+    }
+
+    /// Index of the selected entry, unless none are listed
+    pub fn selected(&self) -> Option<usize> {
+        self.view.get(self.selected).copied()
+    }
+
+    /// Position of the selected entry in the list
+    #[inline]
+    pub fn selected_row(&self) -> usize {
+        self.selected
+    }
+
+    /// Rebuilds the list, keeping the selected entry selected if it's still listed
+    fn update_view(&mut self) {
+        let selected = self.selected();
+        let entries = &self.entries;
+        self.view = (0..entries.len())
+            .filter(|&i| self.filter.as_ref().is_none_or(|q| q.matches(&entries[i])))
+            .collect();
+        match self.sort {
+            SortKey::Position => (),
+            SortKey::Hash => self.view.sort_by_key(|&i| entries[i].entry.hash),
+            SortKey::Size => self.view.sort_by_key(|&i| std::cmp::Reverse(entries[i].entry.data.len())),
+            SortKey::Stages => self.view.sort_by_key(|&i| entries[i].entry.header.map(|h| h.stage_mask)),
+        }
+        self.selected = selected
+            .and_then(|s| self.view.iter().position(|&i| i == s))
+            .unwrap_or(0);
+    }
+
+    pub fn set_sort(&mut self, sort: SortKey) {
+        self.sort = sort;
+        self.update_view();
+    }
+
+    pub fn set_filter(&mut self, filter: Option<Query>) {
+        self.filter = filter;
+        self.update_view();
+    }
+
+    /// Moves the selection by `delta` rows, stopping at either end of the list
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.view.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    /// Selects the next listed entry matching `query`, wrapping around at the end of the list.
+    /// Returns whether there was one.
+    pub fn search(&mut self, query: &Query) -> bool {
+        let len = self.view.len();
+        let found = (1..=len)
+            .map(|d| (self.selected + d) % len)
+            .find(|&row| query.matches(&self.entries[self.view[row]]));
+        if let Some(row) = found {
+            self.selected = row;
+        }
+        found.is_some()
+    }
+
+    /// Marks the selected entry, or unmarks it if it already has this mark
+    pub fn toggle_mark(&mut self, mark: Mark) {
+        if let Some(i) = self.selected() {
+            self.marks[i] = if self.marks[i] == Some(mark) { None } else { Some(mark) };
+        }
+    }
+
+    pub fn marked(&self, mark: Mark) -> usize {
+        self.marks.iter().filter(|&&m| m == Some(mark)).count()
+    }
+
+    fn collect<F: Fn(Option<Mark>) -> bool>(&self, f: F) -> DxvkStateCache {
+        DxvkStateCache {
+            header: self.header,
+            entries: self.entries.iter()
+                .zip(self.marks.iter())
+                .filter(|(e, &m)| e.valid && f(m))
+                .map(|(e, _)| EntryWrapper::from(e.entry.clone()))
+                .collect(),
+        }
+    }
+
+    /// The entries marked for export
+    pub fn exported(&self) -> DxvkStateCache {
+        self.collect(|m| m == Some(Mark::Export))
+    }
+
+    /// The entries not marked for removal
+    pub fn kept(&self) -> DxvkStateCache {
+        self.collect(|m| m != Some(Mark::Remove))
+    }
+}
+
+/// Where [`Browser`]'s marks are written to
+#[derive(Debug, Clone)]
+pub struct Outputs {
+    /// Receives the entries marked for export
+    pub export:   PathBuf,
+    /// Receives the entries not marked for removal
+    pub output:   PathBuf,
+    pub compress: Option<Compression>,
+}
+
+impl Outputs {
+    /// Replaces `path` only once `cache` was written in full, so a failure leaves it as it was
+    fn write(&self, path: &Path, cache: &DxvkStateCache) -> Result<(), io::Error> {
+        store::write_atomic(path, |w| {
+            let mut f = Encoder::new(w, self.compress)?;
+            cache.write_to(&mut f)?;
+            f.finish()?;
+            Ok(())
+        })
+    }
+
+    /// The files [`Outputs::write_marks`] would write, given the marks of `browser`
+    fn targets(&self, browser: &Browser) -> Vec<&Path> {
+        let mut targets = Vec::new();
+        if browser.marked(Mark::Export) > 0 {
+            targets.push(self.export.as_path());
+        }
+        if browser.marked(Mark::Remove) > 0 {
+            targets.push(self.output.as_path());
+        }
+        targets
+    }
+
+    /// The files writing the marks of `browser` would overwrite
+    pub fn existing(&self, browser: &Browser) -> Vec<&Path> {
+        self.targets(browser).into_iter().filter(|p| p.exists()).collect()
+    }
+
+    /// Writes whichever outputs have marked entries, returning what was done. Nothing is written
+    /// if one of them would be left without entries.
+    pub fn write_marks(&self, browser: &Browser) -> Result<String, io::Error> {
+        let exported = Some(browser.exported()).filter(|_| browser.marked(Mark::Export) > 0);
+        let kept = Some(browser.kept()).filter(|_| browser.marked(Mark::Remove) > 0);
+        for (path, cache) in [(&self.export, &exported), (&self.output, &kept)] {
+            if cache.as_ref().is_some_and(|c| c.entries.is_empty()) {
+                return Err(io::Error::other(format!("{} would have no valid entries", path.display())));
+            }
+        }
+        let mut done = Vec::new();
+        if let Some(cache) = exported {
+            self.write(&self.export, &cache)?;
+            done.push(format!("exported {} entries to {}", cache.entries.len(), self.export.display()));
+        }
+        if let Some(cache) = kept {
+            self.write(&self.output, &cache)?;
+            done.push(format!("wrote {} entries to {}", cache.entries.len(), self.output.display()));
+        }
+        if done.is_empty() {
+            done.push("No entries are marked".to_owned());
+        }
+        Ok(done.join(", "))
+    }
+}
+
+/// What typed text will be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Filter,
+    Search,
+}
+
+struct App {
+    browser:  Browser,
+    outputs:  Outputs,
+    name:     String,
+    prompt:   Option<(Prompt, String)>,
+    search:   Option<Query>,
+    status:   String,
+    /// Whether `w` is waiting for the user to confirm overwriting existing files
+    confirm:  bool,
+    /// First row of the list shown
+    offset:   usize,
+    /// Lines the details pane is scrolled by
+    scroll:   u16,
+    /// Rows of the list that fit on screen, as of the last frame
+    height:   usize,
+}
+
+impl App {
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [list, details] = Layout::horizontal([Constraint::Length(72), Constraint::Min(20)]).areas(main);
+        self.draw_list(frame, list);
+
+        let text = match self.browser.selected() {
+            Some(i) => {
+                let mut buf = Vec::new();
+                show::write_details(&mut buf, self.browser.header(), &self.browser.entries()[i]).unwrap();
+                String::from_utf8(buf).unwrap()
+            },
+            None => "No entries match the filter".to_owned(),
+        };
+        let details_block = Block::default().borders(Borders::ALL).title("details");
+        frame.render_widget(Paragraph::new(text).block(details_block).scroll((self.scroll, 0)), details);
+
+        let line = match &self.prompt {
+            Some((Prompt::Filter, text)) => format!("filter: {}", text),
+            Some((Prompt::Search, text)) => format!("/{}", text),
+            None if self.status.is_empty() => HELP.to_owned(),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Line::raw(line), status);
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let b = &self.browser;
+        self.height = (area.height.saturating_sub(3) as usize).max(1);
+        let row = b.selected_row();
+        if row < self.offset {
+            self.offset = row;
+        } else if row >= self.offset + self.height {
+            self.offset = row + 1 - self.height;
+        }
+        let rows = b.view().iter()
+            .skip(self.offset)
+            .take(self.height)
+            .map(|&i| {
+                let e = &b.entries()[i];
+                let mark = match b.mark(i) {
+                    Some(Mark::Export) => "E",
+                    Some(Mark::Remove) => "D",
+                    None => " ",
+                };
+                let stages = e.entry.header
+                    .map(|h| h.stages().to_string())
+                    .unwrap_or_else(|| "-".to_owned());
+                let row = Row::new(vec![
+                    mark.to_owned(),
+                    e.position.index.to_string(),
+                    e.entry.hash_display().to_string(),
+                    stages,
+                    e.entry.data.len().to_string(),
+                ]);
+                // Entries that don't match their hash are listed, but never written out
+                match e.valid {
+                    true => row,
+                    false => row.style(Style::default().fg(Color::Red)),
+                }
+            });
+        let widths = [
+            Constraint::Length(1),
+            Constraint::Length(6),
+            Constraint::Length(40),
+            Constraint::Length(11),
+            Constraint::Length(8),
+        ];
+        let mut title = format!(
+            "{} v{}: {} of {} entries by {}",
+            self.name,
+            b.header().version,
+            b.view().len(),
+            b.entries().len(),
+            b.sort().name()
+        );
+        for (mark, name) in [(Mark::Export, "export"), (Mark::Remove, "remove")] {
+            let n = b.marked(mark);
+            if n > 0 {
+                title.push_str(&format!(", {} to {}", n, name));
+            }
+        }
+        let invalid = b.entries().iter().filter(|e| !e.valid).count();
+        if invalid > 0 {
+            title.push_str(&format!(", {} invalid", invalid));
+        }
+        let table = Table::new(rows, widths)
+            .header(Row::new(vec!["", "#", "hash", "stages", "size"]).style(Style::default().add_modifier(Modifier::BOLD)))
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default();
+        if b.selected().is_some() {
+            state.select(Some(row - self.offset));
+        }
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+
+    /// Handles a key press, returning whether to quit
+    fn on_key(&mut self, key: KeyCode) -> bool {
+        if let Some((prompt, mut text)) = self.prompt.take() {
+            match key {
+                KeyCode::Enter => self.on_prompt(prompt, &text),
+                KeyCode::Esc => (),
+                KeyCode::Backspace => {
+                    text.pop();
+                    self.prompt = Some((prompt, text));
+                },
+                KeyCode::Char(c) => {
+                    text.push(c);
+                    self.prompt = Some((prompt, text));
+                },
+                _ => self.prompt = Some((prompt, text)),
+            }
+            return false;
+        }
+        if self.confirm {
+            self.confirm = false;
+            self.status = match key {
+                KeyCode::Char('y') => self.write(),
+                _ => "Nothing written".to_owned(),
+            };
+            return false;
+        }
+        let before = self.browser.selected();
+        self.status.clear();
+        let page = self.height.max(1) as isize;
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Char('j') | KeyCode::Down => self.browser.move_selection(1),
+            KeyCode::Char('k') | KeyCode::Up => self.browser.move_selection(-1),
+            KeyCode::PageDown => self.browser.move_selection(page),
+            KeyCode::PageUp => self.browser.move_selection(-page),
+            KeyCode::Char('g') | KeyCode::Home => self.browser.move_selection(isize::MIN / 2),
+            KeyCode::Char('G') | KeyCode::End => self.browser.move_selection(isize::MAX / 2),
+            KeyCode::Char('J') => self.scroll = self.scroll.saturating_add(1),
+            KeyCode::Char('K') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Char('o') => self.browser.set_sort(self.browser.sort().next()),
+            KeyCode::Char('f') => self.prompt = Some((Prompt::Filter, String::new())),
+            KeyCode::Char('/') => self.prompt = Some((Prompt::Search, String::new())),
+            KeyCode::Char('n') => match self.search.clone() {
+                Some(query) => self.find(&query),
+                None => self.status = "No search yet".to_owned(),
+            },
+            KeyCode::Char('e') => {
+                self.browser.toggle_mark(Mark::Export);
+                self.browser.move_selection(1);
+            },
+            KeyCode::Char('d') => {
+                self.browser.toggle_mark(Mark::Remove);
+                self.browser.move_selection(1);
+            },
+            KeyCode::Char('w') => {
+                let existing = self.outputs.existing(&self.browser);
+                if existing.is_empty() {
+                    self.status = self.write();
+                } else {
+                    let names: Vec<_> = existing.iter().map(|p| p.display().to_string()).collect();
+                    self.status = format!("Overwrite {}? (y/n)", names.join(" and "));
+                    self.confirm = true;
+                }
+            },
+            _ => (),
+        }
+        if self.browser.selected() != before {
+            self.scroll = 0;
+        }
+        false
+    }
+
+    fn write(&self) -> String {
+        self.outputs.write_marks(&self.browser)
+            .unwrap_or_else(|e| format!("Writing failed: {}", e))
+    }
+
+    fn on_prompt(&mut self, prompt: Prompt, text: &str) {
+        let query = if text.is_empty() {
+            None
+        } else {
+            match text.parse::<Query>() {
+                Ok(query) => Some(query),
+                Err(e) => {
+                    self.status = e;
+                    return;
+                },
+            }
+        };
+        match prompt {
+            Prompt::Filter => self.browser.set_filter(query),
+            Prompt::Search => {
+                if let Some(query) = query {
+                    self.find(&query);
+                    self.search = Some(query);
+                }
+            },
+        }
+        self.scroll = 0;
+    }
+
+    fn find(&mut self, query: &Query) {
+        if !self.browser.search(query) {
+            self.status = "No match".to_owned();
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), io::Error> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && self.on_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Shows the entries of the cache at `path` until the user quits
+pub fn browse(path: &Path, browser: Browser, outputs: Outputs) -> Result<(), io::Error> {
+    let mut app = App {
+        browser: browser,
+        outputs: outputs,
+        name: path.file_name().map_or_else(|| path.display().to_string(), |s| s.to_string_lossy().into_owned()),
+        prompt: None,
+        search: None,
+        status: String::new(),
+        confirm: false,
+        offset: 0,
+        scroll: 0,
+        height: 0,
+    };
+    let mut terminal = ratatui::try_init()?;
+    let res = app.run(&mut terminal);
+    ratatui::restore();
+    res
+}
+
+/// Reads the entries of `path` in the order they're stored
+pub fn open(path: &Path, options: &ReadOptions) -> Result<Browser, ReadError> {
+    let (header, entries) = compress::open(path)
+        .map_err(ReadError::from)
+        .and_then(|r| show::read_entries(r, options))
+        .map_err(|e| e.in_file(path))?;
+    Ok(Browser::new(header, entries))
+}
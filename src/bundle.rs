@@ -0,0 +1,296 @@
+//! Bundles carry the caches of several games in one file, along with who contributed them.
+//!
+//! A bundle starts with the magic string `DXVKBNDL`, the format version and the length of the
+//! index as little-endian `u32`s. The index is a JSON array describing each cache, so listing a
+//! bundle only reads its first few bytes. The caches follow the index in the same order, each
+//! one a complete state cache as [`DxvkStateCache::write_to`] writes it.
+
+use std::{
+    io::{self, Read, Write},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use crate::{
+    compress,
+    dxvk::{DxvkStateCache, ReadError, ReadOptions},
+    store::is_valid_game,
+};
+
+pub const BUNDLE_MAGIC: [u8; 8] = *b"DXVKBNDL";
+pub const BUNDLE_VERSION: u32 = 1;
+/// Largest index that will be read, far more than any realistic number of games needs
+const MAX_INDEX_SIZE: u32 = 16 << 20;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("Not a cache bundle")]
+    MagicMismatch,
+    #[error("Unsupported bundle format version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Bundle index of {0} bytes is too large")]
+    IndexTooLarge(u32),
+    #[error("Invalid bundle index: {0}")]
+    Index(#[from] serde_json::Error),
+    #[error("Invalid game identity \"{0}\" in bundle")]
+    InvalidGame(String),
+    #[error("{game}: {error}")]
+    Read {
+        game:  String,
+        error: ReadError,
+    },
+    #[error("{game}: cache is v{found}, but the index says v{expected}")]
+    IndexMismatch {
+        game:     String,
+        expected: NonZeroU32,
+        found:    NonZeroU32,
+    },
+    #[error("{}: {}", .0.display(), .1)]
+    File(PathBuf, Box<BundleError>),
+}
+
+impl BundleError {
+    /// Attributes the error to the bundle at `path`, unless it names a file already
+    pub fn in_file<P: AsRef<Path>>(self, path: P) -> Self {
+        match self {
+            BundleError::File(..) => self,
+            e => BundleError::File(path.as_ref().into(), Box::new(e)),
+        }
+    }
+
+    /// The bundle the error happened in, if known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            BundleError::File(path, _) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The error without the bundle it happened in
+    pub fn inner(&self) -> &BundleError {
+        match self {
+            BundleError::File(_, e) => e.inner(),
+            e => e,
+        }
+    }
+}
+
+/// Who a cache in a bundle came from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The game's identity, as used by the store
+    pub game:         String,
+    /// DXVK version that wrote the cache, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dxvk_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contributor:  Option<String>,
+    /// When the cache was added to a bundle, in seconds since the Unix epoch
+    pub created:      u64,
+}
+
+impl Metadata {
+    /// Metadata for `game`, created now
+    pub fn new(game: String, dxvk_version: Option<String>, contributor: Option<String>) -> Self {
+        Metadata {
+            game: game,
+            dxvk_version: dxvk_version,
+            contributor: contributor,
+            created: unix_time(),
+        }
+    }
+}
+
+/// The index's description of a single cache
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    #[serde(flatten)]
+    pub metadata:   Metadata,
+    pub version:    NonZeroU32,
+    pub entry_size: u32,
+    pub entries:    usize,
+    /// Where the cache starts, counting from the end of the index
+    pub offset:     u64,
+    pub length:     u64,
+}
+
+/// Writes a bundle holding `caches`. Each cache is serialized up front, since the index in front
+/// of them needs their lengths.
+pub fn write_bundle<W: Write>(mut writer: W, caches: &[(Metadata, DxvkStateCache)]) -> Result<(), BundleError> {
+    let mut index = Vec::with_capacity(caches.len());
+    let mut bodies = Vec::with_capacity(caches.len());
+    let mut offset = 0;
+    for (metadata, cache) in caches.iter() {
+        if !is_valid_game(&metadata.game) {
+            return Err(BundleError::InvalidGame(metadata.game.clone()));
+        }
+        let mut body = Vec::new();
+        cache.write_to(&mut body)?;
+        index.push(IndexEntry {
+            metadata: metadata.clone(),
+            version: cache.header.version,
+            entry_size: cache.header.entry_size,
+            entries: cache.entries.len(),
+            offset: offset,
+            length: body.len() as u64,
+        });
+        offset += body.len() as u64;
+        bodies.push(body);
+    }
+    let index = serde_json::to_vec(&index)?;
+    writer.write_all(&BUNDLE_MAGIC)?;
+    writer.write_u32::<LittleEndian>(BUNDLE_VERSION)?;
+    writer.write_u32::<LittleEndian>(index.len() as u32)?;
+    writer.write_all(&index)?;
+    for body in bodies.iter() {
+        writer.write_all(body)?;
+    }
+    Ok(())
+}
+
+/// Reads a bundle's caches in the order they're stored
+pub struct BundleReader<R> {
+    reader:  R,
+    index:   Vec<IndexEntry>,
+    options: ReadOptions,
+    next:    usize,
+    /// Bytes read past the end of the index
+    pos:     u64,
+    /// The bundle's file, which errors are attributed to
+    path:    Option<PathBuf>,
+}
+
+impl<R: Read> BundleReader<R> {
+    /// Reads the bundle's index, leaving the caches for [`BundleReader::next_cache`]
+    pub fn new(mut reader: R, options: &ReadOptions) -> Result<Self, BundleError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != BUNDLE_MAGIC {
+            return Err(BundleError::MagicMismatch);
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(version));
+        }
+        let index_size = reader.read_u32::<LittleEndian>()?;
+        if index_size > MAX_INDEX_SIZE {
+            return Err(BundleError::IndexTooLarge(index_size));
+        }
+        let mut index = Vec::new();
+        (&mut reader).take(index_size as u64).read_to_end(&mut index)?;
+        if index.len() != index_size as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let index: Vec<IndexEntry> = serde_json::from_slice(&index)?;
+        if let Some(e) = index.iter().find(|e| !is_valid_game(&e.metadata.game)) {
+            return Err(BundleError::InvalidGame(e.metadata.game.clone()));
+        }
+        Ok(BundleReader {
+            reader: reader,
+            index: index,
+            options: *options,
+            next: 0,
+            pos: 0,
+            path: None,
+        })
+    }
+
+    #[inline]
+    pub fn index(&self) -> &[IndexEntry] {
+        &self.index
+    }
+
+    /// Reads the next cache along with its index entry
+    pub fn next_cache(&mut self) -> Result<Option<(IndexEntry, DxvkStateCache)>, BundleError> {
+        self.read_next().map_err(|e| match self.path.as_ref() {
+            Some(path) => e.in_file(path),
+            None => e,
+        })
+    }
+
+    fn read_next(&mut self) -> Result<Option<(IndexEntry, DxvkStateCache)>, BundleError> {
+        let entry = match self.index.get(self.next) {
+            Some(v) => v.clone(),
+            None => return Ok(None),
+        };
+        self.next += 1;
+        // Skip anything between the previous cache and this one
+        let gap = entry.offset.checked_sub(self.pos)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "overlapping caches in bundle"))?;
+        io::copy(&mut (&mut self.reader).take(gap), &mut io::sink())?;
+        let mut body = (&mut self.reader).take(entry.length);
+        let cache = DxvkStateCache::from_reader_with(&mut body, &self.options)
+            .map_err(|e| BundleError::Read {
+                game: entry.metadata.game.clone(),
+                error: e,
+            })?;
+        io::copy(&mut body, &mut io::sink())?;
+        if body.limit() > 0 {
+            return Err(BundleError::Read {
+                game: entry.metadata.game,
+                error: io::Error::from(io::ErrorKind::UnexpectedEof).into(),
+            });
+        }
+        self.pos = entry.offset + entry.length;
+        if cache.header.version != entry.version {
+            return Err(BundleError::IndexMismatch {
+                game: entry.metadata.game,
+                expected: entry.version,
+                found: cache.header.version,
+            });
+        }
+        Ok(Some((entry, cache)))
+    }
+}
+
+impl<R: Read> Iterator for BundleReader<R> {
+    type Item = Result<(IndexEntry, DxvkStateCache), BundleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_cache().transpose()
+    }
+}
+
+/// Opens a bundle, decompressing it if needed. Errors reading it, then and later, name its path.
+pub fn open<P: AsRef<Path>>(path: P, options: &ReadOptions) -> Result<BundleReader<Box<dyn Read>>, BundleError> {
+    let path = path.as_ref();
+    let mut reader = compress::open(path)
+        .map_err(BundleError::from)
+        .and_then(|r| BundleReader::new(r, options))
+        .map_err(|e| e.in_file(path))?;
+    reader.path = Some(path.into());
+    Ok(reader)
+}
+
+/// The current time in seconds since the Unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats seconds since the Unix epoch as an ISO 8601 UTC timestamp
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, time / 3600, time / 60 % 60, time % 60
+    )
+}
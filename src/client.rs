@@ -0,0 +1,141 @@
+//! Client for the API [`crate::server`] exposes
+
+use std::{
+    fs,
+    io,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use crate::{
+    dxvk::{DxvkStateCache, ReadError, ReadOptions},
+    server::{percent_encode, GAMES_PATH},
+    store::{AddSummary, StoredGame},
+};
+
+/// How long to wait for the server to accept a connection
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the server to send or accept more data
+pub const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("{url}: server responded with {status}: {message}")]
+    Status {
+        url:     String,
+        status:  u16,
+        message: String,
+    },
+    #[error("{0}")]
+    Transport(Box<ureq::Transport>),
+    #[error("{}: {}", .0.display(), .1)]
+    Io(PathBuf, io::Error),
+    #[error("{0}: {1}")]
+    Read(String, ReadError),
+    #[error("{0}: invalid response: {1}")]
+    Response(String, String),
+}
+
+impl ClientError {
+    fn from_ureq(url: &str, e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(status, response) => {
+                // The server explains errors in a JSON object
+                let body = response.into_string().unwrap_or_default();
+                let message = serde_json::from_str::<serde_json::Value>(&body)
+                    .ok()
+                    .and_then(|v| v["error"].as_str().map(String::from))
+                    .unwrap_or(body);
+                ClientError::Status {
+                    url: url.into(),
+                    status: status,
+                    message: message,
+                }
+            },
+            ureq::Error::Transport(e) => ClientError::Transport(Box::new(e)),
+        }
+    }
+}
+
+pub struct Client {
+    base:    String,
+    options: ReadOptions,
+    agent:   ureq::Agent,
+}
+
+impl Client {
+    /// A client for the server at `base`, e.g. `http://hub:8420`. Downloaded caches are parsed
+    /// with `options`.
+    pub fn new(base: &str, options: ReadOptions) -> Self {
+        Self::with_timeouts(base, options, CONNECT_TIMEOUT, IO_TIMEOUT)
+    }
+
+    /// Like [`Client::new`], giving up on a server that takes longer than `connect` to accept the
+    /// connection, or stalls for longer than `io` while sending or receiving
+    pub fn with_timeouts(base: &str, options: ReadOptions, connect: Duration, io: Duration) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(connect)
+            .timeout_read(io)
+            .timeout_write(io)
+            .build();
+        Client {
+            base: base.trim_end_matches('/').to_owned(),
+            options: options,
+            agent: agent,
+        }
+    }
+
+    fn game_url(&self, game: &str) -> String {
+        format!("{}{}/{}", self.base, GAMES_PATH, percent_encode(game))
+    }
+
+    fn json(url: &str, response: ureq::Response) -> Result<serde_json::Value, ClientError> {
+        serde_json::from_reader(response.into_reader())
+            .map_err(|e| ClientError::Response(url.into(), e.to_string()))
+    }
+
+    pub fn list(&self) -> Result<Vec<StoredGame>, ClientError> {
+        let url = format!("{}{}", self.base, GAMES_PATH);
+        let response = self.agent.get(&url).call().map_err(|e| ClientError::from_ureq(&url, e))?;
+        let invalid = || ClientError::Response(url.clone(), "malformed game list".into());
+        Self::json(&url, response)?
+            .as_array()
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|game| {
+                Some(StoredGame {
+                    game: game["game"].as_str()?.to_owned(),
+                    version: NonZeroU32::new(game["version"].as_u64()? as u32)?,
+                    entries: game["entries"].as_u64()? as usize,
+                })
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)
+    }
+
+    /// Downloads the cache stored for `game`, checking it the same way local caches are
+    pub fn pull(&self, game: &str) -> Result<DxvkStateCache, ClientError> {
+        let url = self.game_url(game);
+        let response = self.agent.get(&url).call().map_err(|e| ClientError::from_ureq(&url, e))?;
+        DxvkStateCache::from_reader_with(response.into_reader(), &self.options)
+            .map_err(|e| ClientError::Read(url, e))
+    }
+
+    /// Uploads the cache at `path` to be merged into the one stored for `game`
+    pub fn push(&self, game: &str, path: &Path) -> Result<AddSummary, ClientError> {
+        let url = self.game_url(game);
+        let body = fs::read(path).map_err(|e| ClientError::Io(path.into(), e))?;
+        let response = self.agent.put(&url)
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(&body)
+            .map_err(|e| ClientError::from_ureq(&url, e))?;
+        let summary = Self::json(&url, response)?;
+        match (summary["added"].as_u64(), summary["total"].as_u64()) {
+            (Some(added), Some(total)) => Ok(AddSummary {
+                added: added as usize,
+                total: total as usize,
+            }),
+            _ => Err(ClientError::Response(url, "malformed merge summary".into())),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Compressed state caches. Inputs are recognised by their magic bytes, whatever their file
+//! name, so every command reads them transparently.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Zstd,
+    Xz,
+    Gzip,
+}
+
+impl Compression {
+    /// Recognises the format from the first bytes of a file
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else if magic.starts_with(&XZ_MAGIC) {
+            Some(Compression::Xz)
+        } else if magic.starts_with(&GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+            Compression::Gzip => "gzip",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "xz" => Ok(Compression::Xz),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            _ => Err(format!("unknown compression \"{}\"", s)),
+        }
+    }
+}
+
+/// The compression `reader`'s contents use, without consuming anything
+pub fn peek<R: BufRead>(reader: &mut R) -> io::Result<Option<Compression>> {
+    Ok(Compression::detect(reader.fill_buf()?))
+}
+
+/// Wraps `reader` in a decoder if its contents are compressed
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match peek(&mut reader)? {
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::with_buffer(reader)?),
+        Some(Compression::Xz) => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        Some(Compression::Gzip) => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        None => Box::new(reader),
+    })
+}
+
+/// Opens a state cache for reading, decompressing it if needed
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read>> {
+    decompress(BufReader::new(File::open(path)?))
+}
+
+/// A writer that compresses what's written to it, or passes it through unchanged
+pub enum Encoder<W: Write> {
+    Plain(W),
+    Zstd(zstd::Encoder<'static, W>),
+    Xz(xz2::write::XzEncoder<W>),
+    Gzip(flate2::write::GzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(writer: W, compression: Option<Compression>) -> io::Result<Self> {
+        Ok(match compression {
+            None => Encoder::Plain(writer),
+            Some(Compression::Zstd) => Encoder::Zstd(zstd::Encoder::new(writer, 19)?),
+            Some(Compression::Xz) => Encoder::Xz(xz2::write::XzEncoder::new(writer, 9)),
+            Some(Compression::Gzip) => {
+                Encoder::Gzip(flate2::write::GzEncoder::new(writer, flate2::Compression::best()))
+            },
+        })
+    }
+
+    /// Writes the end of the compressed stream, which dropping the encoder wouldn't
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = match self {
+            Encoder::Plain(w) => w,
+            Encoder::Zstd(w) => w.finish()?,
+            Encoder::Xz(w) => w.finish()?,
+            Encoder::Gzip(w) => w.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(w) => w.write(buf),
+            Encoder::Zstd(w) => w.write(buf),
+            Encoder::Xz(w) => w.write(buf),
+            Encoder::Gzip(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(w) => w.flush(),
+            Encoder::Zstd(w) => w.flush(),
+            Encoder::Xz(w) => w.flush(),
+            Encoder::Gzip(w) => w.flush(),
+        }
+    }
+}
@@ -9,32 +9,42 @@ use std::{
         Hasher,
     },
     num::NonZeroU32,
+    ops::RangeInclusive,
     io::{
         self,
         Read,
         Write,
     },
     fmt,
-    path::Path,
-    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 use byteorder::{
     ReadBytesExt,
     WriteBytesExt,
-    NativeEndian,
+    BigEndian,
+    LittleEndian,
 };
+use log::debug;
 use crate::{
+    compress,
     read::FromReader,
 };
 
 pub type Sha1Hash = [u8; HASH_SIZE];
 pub const LEGACY_VERSION: u32 = 7;
 pub const HASH_SIZE: usize = 20;
+/// Largest entry size representable in a standard entry header's 24-bit size field
+pub const MAX_ENTRY_SIZE: u32 = (1 << 24) - 1;
 pub const MAGIC_STRING: [u8; 4] = *b"DXVK";
 const SHA1_EMPTY: Sha1Hash = [
     218, 57, 163, 238, 94, 107, 75, 13, 50, 85, 191, 239, 149, 96, 24, 144, 175, 216, 7, 9
 ];
-type DxvkEndian = NativeEndian;
+/// Highest version number considered plausible when detecting a header's byte order
+const MAX_PLAUSIBLE_VERSION: u32 = 0xffff;
+/// Bounds on the fixed entry size of legacy caches, including the trailing hash. Legacy entries
+/// are a plain struct of a few kilobytes, so anything outside of this is a corrupt header.
+const LEGACY_ENTRY_SIZE_BOUNDS: RangeInclusive<u32> = (HASH_SIZE as u32 + 1)..=(1 << 16);
 type EntryHash = [u8; HASH_SIZE];
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -50,20 +60,145 @@ impl Default for DxvkStateCacheEdition {
     }
 }
 
+/// Byte order of the integers in a state cache.
+///
+/// DXVK always produces little-endian caches, and that's what gets written back out. Big-endian
+/// input only shows up when a cache was written by a tool that used the host's native byte order
+/// on a big-endian machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big
+}
+
+impl Default for Endian {
+    #[inline(always)]
+    fn default() -> Self {
+        Endian::Little
+    }
+}
+
+impl FromStr for Endian {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "little" | "le" => Ok(Endian::Little),
+            "big" | "be" => Ok(Endian::Big),
+            _ => Err(format!("invalid byte order \"{}\", expected \"little\" or \"big\"", s)),
+        }
+    }
+}
+
+impl Endian {
+    fn read_u32<R: Read>(self, mut reader: R) -> io::Result<u32> {
+        match self {
+            Endian::Little => reader.read_u32::<LittleEndian>(),
+            Endian::Big => reader.read_u32::<BigEndian>(),
+        }
+    }
+
+    fn read_u24<R: Read>(self, mut reader: R) -> io::Result<u32> {
+        match self {
+            Endian::Little => reader.read_u24::<LittleEndian>(),
+            Endian::Big => reader.read_u24::<BigEndian>(),
+        }
+    }
+
+    /// Guesses the byte order of a header from the raw bytes of its version field
+    fn detect(version: [u8; 4]) -> Result<Self, HeaderError> {
+        let little = u32::from_le_bytes(version);
+        let big = u32::from_be_bytes(version);
+        if little == 0 {
+            Err(HeaderError::InvalidVersion)
+        } else if little <= MAX_PLAUSIBLE_VERSION {
+            Ok(Endian::Little)
+        } else if big <= MAX_PLAUSIBLE_VERSION {
+            Err(HeaderError::ByteSwapped(big))
+        } else {
+            Err(HeaderError::ImplausibleVersion(little))
+        }
+    }
+}
+
+/// Limits on how much memory parsing a single state cache may allocate
+#[derive(Debug, Clone, Copy)]
+pub struct ReadLimits {
+    /// Largest entry payload that will be allocated, in bytes
+    pub max_entry_size: usize,
+    /// Largest combined payload of all entries in a cache, in bytes
+    pub max_cache_size: u64,
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        ReadLimits {
+            max_entry_size: 1 << 24,
+            max_cache_size: 4 << 30,
+        }
+    }
+}
+
+/// Options controlling how state caches are parsed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadOptions {
+    /// Byte order to read input with. Detected from the header if unset.
+    pub endian: Option<Endian>,
+    pub limits: ReadLimits,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct DxvkStateCacheHeader {
     pub magic:      [u8; 4],
     pub version:    NonZeroU32,
-    pub entry_size: u32
+    pub entry_size: u32,
+    /// Byte order the cache was read with. Caches are always written little-endian.
+    pub endian:     Endian
 }
 
 impl DxvkStateCacheHeader {
+    /// Size of the header as stored on disk
+    pub const SIZE: usize = 12;
+
     pub const fn new(version: NonZeroU32, entry_size: u32) -> Self {
         DxvkStateCacheHeader {
             magic: MAGIC_STRING,
             version: version,
             entry_size: entry_size,
+            endian: Endian::Little,
+        }
+    }
+
+    /// Range of valid top-level entry sizes for this header's version. Standard caches store a
+    /// size per entry and ignore the top-level one.
+    pub fn entry_size_bounds(&self) -> RangeInclusive<u32> {
+        match self.edition() {
+            DxvkStateCacheEdition::Legacy => LEGACY_ENTRY_SIZE_BOUNDS,
+            DxvkStateCacheEdition::Standard => 0..=u32::MAX,
+        }
+    }
+
+    /// Whether entries from a cache with the `other` header can be stored in this one
+    #[inline]
+    pub fn is_compatible(&self, other: &DxvkStateCacheHeader) -> bool {
+        self.version == other.version && self.entry_size == other.entry_size
+    }
+
+    /// Like [`is_compatible`](Self::is_compatible), but tells what differs
+    pub fn check_compatible(&self, other: &DxvkStateCacheHeader) -> Result<(), ReadError> {
+        if other.version != self.version {
+            return Err(ReadError::VersionMismatch {
+                found: other.version,
+                expected: self.version,
+            });
         }
+        if other.entry_size != self.entry_size {
+            return Err(ReadError::EntrySizeMismatch {
+                found: other.entry_size,
+                expected: self.entry_size,
+            });
+        }
+        Ok(())
     }
 
     #[inline]
@@ -76,9 +211,9 @@ impl DxvkStateCacheHeader {
     }
 
     pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&MAGIC_STRING)?;
-        writer.write_u32::<DxvkEndian>(self.version.get())?;
-        writer.write_u32::<DxvkEndian>(self.entry_size)?;
+        writer.write_all(&self.magic)?;
+        writer.write_u32::<LittleEndian>(self.version.get())?;
+        writer.write_u32::<LittleEndian>(self.entry_size)?;
         Ok(())
     }
 }
@@ -87,35 +222,66 @@ impl DxvkStateCacheHeader {
 pub enum HeaderError {
     #[error("{0}")]
     Io(#[from] io::Error),
-    #[error("Magic string mismatch")]
+    #[error("magic string mismatch")]
     MagicStringMismatch,
-    #[error("Header contained invalid zero version")]
+    #[error("invalid zero version")]
     InvalidVersion,
+    #[error("appears to be byte-swapped (big-endian v{0}); use --assume-endian big to read it")]
+    ByteSwapped(u32),
+    #[error("implausible version {0:#x} in either byte order")]
+    ImplausibleVersion(u32),
+    #[error("entry size {entry_size} is out of range for v{version}")]
+    InvalidEntrySize {
+        version: NonZeroU32,
+        entry_size: u32,
+    },
 }
 
 impl FromReader for DxvkStateCacheHeader {
     type Error = HeaderError;
-    fn from_reader<R>(mut reader: R) -> Result<Self, Self::Error>
+    #[inline(always)]
+    fn from_reader<R>(reader: R) -> Result<Self, Self::Error>
     where
         R: Read,
     {
-        Ok(DxvkStateCacheHeader {
-            magic: {
-                let mut magic = [0u8; 4];
-                reader.read_exact(&mut magic)?;
-                if magic != MAGIC_STRING {
-                    return Err(HeaderError::MagicStringMismatch);
-                }
-                magic
-            },
+        Self::from_reader_with(reader, &ReadOptions::default())
+    }
+}
+
+impl DxvkStateCacheHeader {
+    pub fn from_reader_with<R>(mut reader: R, options: &ReadOptions) -> Result<Self, HeaderError>
+    where
+        R: Read,
+    {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC_STRING {
+            return Err(HeaderError::MagicStringMismatch);
+        }
+        let mut version = [0u8; 4];
+        reader.read_exact(&mut version)?;
+        let endian = match options.endian {
+            Some(e) => e,
+            None => Endian::detect(version)?,
+        };
+        let header = DxvkStateCacheHeader {
+            magic: magic,
             version: {
-                let v = reader.read_u32::<DxvkEndian>()?;
+                let v = endian.read_u32(&version[..])?;
                 NonZeroU32::new(v)
                     .map(Ok)
                     .unwrap_or(Err(HeaderError::InvalidVersion))?
             },
-            entry_size: reader.read_u32::<DxvkEndian>()?,
-        })
+            entry_size: endian.read_u32(&mut reader)?,
+            endian: endian,
+        };
+        if !header.entry_size_bounds().contains(&header.entry_size) {
+            return Err(HeaderError::InvalidEntrySize {
+                version: header.version,
+                entry_size: header.entry_size,
+            });
+        }
+        Ok(header)
     }
 }
 
@@ -134,28 +300,61 @@ impl fmt::Debug for DxvkStateCacheEntryHeader {
     }
 }
 
-impl FromReader for DxvkStateCacheEntryHeader {
-    type Error = io::Error;
-    fn from_reader<R>(mut reader: R) -> Result<Self, Self::Error>
+impl DxvkStateCacheEntryHeader {
+    pub fn from_reader<R>(mut reader: R, endian: Endian) -> Result<Self, io::Error>
     where
         R: Read,
     {
         Ok(DxvkStateCacheEntryHeader {
             stage_mask: reader.read_u8()?,
-            entry_size: reader.read_u24::<DxvkEndian>()?,
+            entry_size: endian.read_u24(&mut reader)?,
         })
     }
-}
 
-impl DxvkStateCacheEntryHeader {
+
+    /// Size of the entry header as stored on disk
+    pub const SIZE: usize = 4;
+
+    #[inline(always)]
+    pub fn stages(&self) -> StageMaskDisplay {
+        StageMaskDisplay(self.stage_mask)
+    }
+
     pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), io::Error> {
         writer.write_u8(self.stage_mask)?;
-        writer.write_u24::<DxvkEndian>(self.entry_size)?;
+        writer.write_u24::<LittleEndian>(self.entry_size)?;
         Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
+/// Names of the shader stages, in the order of their bits in a stage mask
+pub const STAGE_NAMES: [&str; 6] = ["VS", "TCS", "TES", "GS", "FS", "CS"];
+
+/// Shows a stage mask as the names of its stages, like `VS|FS`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageMaskDisplay(pub u8);
+
+impl fmt::Display for StageMaskDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return write!(f, "none");
+        }
+        let mut sep = "";
+        for (bit, name) in STAGE_NAMES.iter().enumerate() {
+            if self.0 & (1 << bit) != 0 {
+                write!(f, "{}{}", sep, name)?;
+                sep = "|";
+            }
+        }
+        let unknown = self.0 & !((1 << STAGE_NAMES.len()) - 1);
+        if unknown != 0 {
+            write!(f, "{}{:#x}", sep, unknown)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DxvkStateCacheEntry {
     pub header: Option<DxvkStateCacheEntryHeader>,
     pub hash:   [u8; HASH_SIZE],
@@ -166,48 +365,94 @@ pub struct DxvkStateCacheEntry {
 pub enum EntryError {
     #[error("{0}")]
     Io(#[from] io::Error),
-    #[error("Entry invalid due to hash mismatch")]
+    #[error("hash mismatch")]
     HashMismatch,
+    #[error("size of {size} bytes exceeds the limit of {limit} bytes")]
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+    #[error("size of {0} bytes is too small to hold its hash")]
+    TooSmall(usize),
+    #[error("entries exceed the total size limit of {0} bytes")]
+    CacheTooLarge(u64),
+    #[error("cut short by the end of the file")]
+    Truncated,
+}
+
+/// Reads exactly `len` bytes without allocating them up front, so a bogus size in a truncated
+/// file fails at the end of the file rather than at allocation time
+fn read_data<R: Read>(reader: R, len: usize) -> Result<Vec<u8>, io::Error> {
+    let mut data = Vec::new();
+    reader.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
 }
 
 impl DxvkStateCacheEntry {
-    fn from_reader_legacy<R>(mut reader: R, size: usize) -> Result<Self, io::Error>
+    fn from_reader_legacy<R>(mut reader: R, size: usize, limits: &ReadLimits) -> Result<Self, EntryError>
     where
         R: Read,
     {
-        let mut entry = DxvkStateCacheEntry::with_length(size);
-        reader.read_exact(&mut entry.data)?;
+        let data_size = size.checked_sub(HASH_SIZE)
+            .ok_or(EntryError::TooSmall(size))?;
+        check_entry_size(data_size, limits)?;
+        let mut entry = DxvkStateCacheEntry::with_data(read_data(&mut reader, data_size)?, None);
         reader.read_exact(&mut entry.hash)?;
         Ok(entry)
     }
 
-    fn from_reader_standard<R>(mut reader: R) -> Result<Self, io::Error>
+    fn from_reader_standard<R>(mut reader: R, endian: Endian, limits: &ReadLimits) -> Result<Self, EntryError>
     where
         R: Read,
     {
-        let header = DxvkStateCacheEntryHeader::from_reader(&mut reader)?;
-        let mut entry = DxvkStateCacheEntry::with_header(header);
-        reader.read_exact(&mut entry.hash)?;
-        reader.read_exact(&mut entry.data)?;
+        let header = DxvkStateCacheEntryHeader::from_reader(&mut reader, endian)?;
+        check_entry_size(header.entry_size as usize, limits)?;
+        let mut hash = [0; HASH_SIZE];
+        reader.read_exact(&mut hash)?;
+        let mut entry = DxvkStateCacheEntry::with_data(
+            read_data(&mut reader, header.entry_size as usize)?,
+            Some(header)
+        );
+        entry.hash = hash;
         Ok(entry)
     }
 
-    pub fn from_reader<R>(reader: R, top_header: &DxvkStateCacheHeader) -> Result<Self, EntryError>
+    pub fn from_reader<R>(
+        reader: R,
+        top_header: &DxvkStateCacheHeader,
+        limits: &ReadLimits
+    ) -> Result<Self, EntryError>
     where
         R: Read,
     {
-        let ret = match top_header.edition() {
-            DxvkStateCacheEdition::Standard =>
-                Self::from_reader_standard(reader),
-            DxvkStateCacheEdition::Legacy =>
-                Self::from_reader_legacy(reader, top_header.entry_size as usize),
-        }?;
+        let ret = Self::from_reader_unverified(reader, top_header, limits)?;
         if !ret.is_valid() {
             return Err(EntryError::HashMismatch);
         }
         Ok(ret)
     }
 
+    /// Like [`from_reader`](Self::from_reader), but also returns entries that don't match their
+    /// hash
+    fn from_reader_unverified<R>(
+        reader: R,
+        top_header: &DxvkStateCacheHeader,
+        limits: &ReadLimits
+    ) -> Result<Self, EntryError>
+    where
+        R: Read,
+    {
+        match top_header.edition() {
+            DxvkStateCacheEdition::Standard =>
+                Self::from_reader_standard(reader, top_header.endian, limits),
+            DxvkStateCacheEdition::Legacy =>
+                Self::from_reader_legacy(reader, top_header.entry_size as usize, limits),
+        }
+    }
+
     fn write_standard<W>(&self, mut writer: W) -> Result<(), io::Error>
     where
         W: Write,
@@ -224,8 +469,8 @@ impl DxvkStateCacheEntry {
     where
         W: Write,
     {
-        writer.write_all(&self.hash)
-            .and_then(|_| writer.write_all(&self.data))
+        writer.write_all(&self.data)
+            .and_then(|_| writer.write_all(&self.hash))
     }
 
     pub fn write_to<W: Write>(&self, w: W, edition: DxvkStateCacheEdition) -> Result<(), io::Error> {
@@ -235,57 +480,246 @@ impl DxvkStateCacheEntry {
         }
     }
 
+    /// Number of bytes this entry occupies when written to a state cache
+    pub fn size_on_disk(&self) -> usize {
+        let header_size = self.header
+            .map(|_| DxvkStateCacheEntryHeader::SIZE)
+            .unwrap_or(0);
+        header_size + HASH_SIZE + self.data.len()
+    }
+
     #[inline(always)]
     pub fn hash_display<'a>(&'a self) -> HashDisplay<'a> {
         HashDisplay(&self.hash)
     }
 }
 
+#[inline]
+fn check_entry_size(size: usize, limits: &ReadLimits) -> Result<(), EntryError> {
+    if size > limits.max_entry_size {
+        return Err(EntryError::TooLarge {
+            size: size,
+            limit: limits.max_entry_size,
+        });
+    }
+    Ok(())
+}
+
 impl DxvkStateCacheEntry {
-    fn with_length(length: usize) -> Self {
+    fn with_data(data: Vec<u8>, header: Option<DxvkStateCacheEntryHeader>) -> Self {
         DxvkStateCacheEntry {
-            data:   vec![0; length - HASH_SIZE],
+            data:   data,
             hash:   [0; HASH_SIZE],
-            header: None
+            header: header
         }
     }
 
-    fn with_header(header: DxvkStateCacheEntryHeader) -> Self {
-        DxvkStateCacheEntry {
-            data:   vec![0; header.entry_size as usize],
-            hash:   [0; HASH_SIZE],
-            header: Some(header)
-        }
+    /// Creates an entry holding `data`, hashed the way DXVK hashes entries of `edition`. The
+    /// stage mask is only stored by standard caches.
+    pub fn new(data: Vec<u8>, stage_mask: u8, edition: DxvkStateCacheEdition) -> Self {
+        let header = match edition {
+            DxvkStateCacheEdition::Standard => Some(DxvkStateCacheEntryHeader {
+                stage_mask: stage_mask,
+                entry_size: data.len() as u32,
+            }),
+            DxvkStateCacheEdition::Legacy => None,
+        };
+        let mut entry = DxvkStateCacheEntry::with_data(data, header);
+        entry.hash = entry.compute_hash();
+        entry
     }
 
-    pub fn is_valid(&self) -> bool {
+    fn compute_hash(&self) -> EntryHash {
         let mut hasher = Sha1::default();
         hasher.update(&self.data);
         if self.header.is_none() {
-            hasher.update(&SHA1_EMPTY);
+            hasher.update(SHA1_EMPTY);
         }
         let hash = hasher.finalize();
-        let hash: EntryHash = unsafe { std::mem::transmute(hash) };
+        unsafe { std::mem::transmute(hash) }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.compute_hash() == self.hash
+    }
+}
+
+/// Where an entry starts in its cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryPosition {
+    /// Number of entries before it, counting invalid ones
+    pub index:  usize,
+    /// Byte offset from the start of the cache, header included
+    pub offset: u64,
+}
+
+impl fmt::Display for EntryPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "entry #{} @{:#x}", self.index, self.offset)
+    }
+}
+
+/// Counts the bytes read through it
+struct CountingReader<R> {
+    reader: R,
+    count:  u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// Reads the entries following a state cache header, enforcing the configured [`ReadLimits`]
+pub struct EntryReader<R> {
+    reader: CountingReader<R>,
+    header: DxvkStateCacheHeader,
+    limits: ReadLimits,
+    total_size: u64,
+    position: EntryPosition,
+    next_index: usize,
+}
+
+impl<R: Read> EntryReader<R> {
+    pub fn new(reader: R, header: DxvkStateCacheHeader, limits: ReadLimits) -> Self {
+        EntryReader {
+            reader: CountingReader {
+                reader: reader,
+                count: 0,
+            },
+            header: header,
+            limits: limits,
+            total_size: 0,
+            position: EntryPosition {
+                index: 0,
+                offset: DxvkStateCacheHeader::SIZE as u64,
+            },
+            next_index: 0,
+        }
+    }
 
-        hash == self.hash
+    /// Where the entry last returned, or failed to be read, starts
+    #[inline]
+    pub fn position(&self) -> EntryPosition {
+        self.position
+    }
+
+    /// Reads the next entry, returning `None` once the end of the cache is reached. Running out
+    /// of data partway through an entry is an error rather than the end of the cache.
+    pub fn next_entry(&mut self) -> Result<Option<DxvkStateCacheEntry>, EntryError> {
+        match self.next_entry_unverified()? {
+            Some(entry) if !entry.is_valid() => Err(EntryError::HashMismatch),
+            entry => Ok(entry),
+        }
+    }
+
+    /// Like [`next_entry`](Self::next_entry), but also returns entries that don't match their
+    /// hash, for looking into damaged caches
+    pub fn next_entry_unverified(&mut self) -> Result<Option<DxvkStateCacheEntry>, EntryError> {
+        let start = self.reader.count;
+        self.position = EntryPosition {
+            index: self.next_index,
+            offset: DxvkStateCacheHeader::SIZE as u64 + start,
+        };
+        self.next_index += 1;
+        let entry = match DxvkStateCacheEntry::from_reader_unverified(&mut self.reader, &self.header, &self.limits) {
+            Ok(v) => v,
+            Err(EntryError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                if self.reader.count == start {
+                    return Ok(None);
+                }
+                return Err(EntryError::Truncated);
+            },
+            Err(e) => return Err(e),
+        };
+        self.total_size += entry.data.len() as u64;
+        if self.total_size > self.limits.max_cache_size {
+            return Err(EntryError::CacheTooLarge(self.limits.max_cache_size));
+        }
+        Ok(Some(entry))
     }
 }
 
+/// Reads the cache at `path`, leaving out the entries that don't match their hash instead of
+/// failing. Returns the other entries in file order, along with how many were left out.
+pub fn read_valid_entries(
+    path: &Path,
+    options: &ReadOptions,
+) -> Result<(DxvkStateCacheHeader, Vec<DxvkStateCacheEntry>, usize), ReadError> {
+    let read = || {
+        let mut reader = compress::open(path)?;
+        let header = DxvkStateCacheHeader::from_reader_with(&mut reader, options)?;
+        let mut reader = EntryReader::new(reader, header, options.limits);
+        let mut entries = Vec::new();
+        let mut omitted = 0;
+        loop {
+            match reader.next_entry() {
+                Ok(Some(e)) => entries.push(e),
+                Ok(None) => break,
+                Err(EntryError::HashMismatch) => {
+                    debug!("{}: {}: hash mismatch, omitted", path.display(), reader.position());
+                    omitted += 1;
+                },
+                Err(e) => return Err(ReadError::ReadEntry(reader.position(), e)),
+            }
+        }
+        Ok((header, entries, omitted))
+    };
+    read().map_err(|e| e.in_file(path))
+}
+
+/// Everything that can go wrong reading a state cache, along with where it went wrong
 #[derive(Debug, thiserror::Error)]
 pub enum ReadError {
     #[error("{0}")]
     Io(#[from] io::Error),
-    #[error("Error reading header: {0}")]
+    #[error("header: {0}")]
     ReadHeader(#[from] HeaderError),
-    #[error("Error reading entry: {0}")]
-    ReadEntry(#[from] EntryError),
-    #[error("Duplicate entry in state cache")]
-    DuplicateEntry,
+    #[error("{0}: {1}")]
+    ReadEntry(EntryPosition, EntryError),
+    #[error("{0}: duplicate entry")]
+    DuplicateEntry(EntryPosition),
     #[error("version mismatch: found v{found}, expected v{expected}")]
     VersionMismatch {
         found: NonZeroU32,
         expected: NonZeroU32,
     },
+    #[error("entry size mismatch: found {found}, expected {expected}")]
+    EntrySizeMismatch {
+        found: u32,
+        expected: u32,
+    },
+    #[error("{}: {}", .0.display(), .1)]
+    File(PathBuf, Box<ReadError>),
+}
+
+impl ReadError {
+    /// Attributes the error to the file at `path`, unless it names a file already
+    pub fn in_file<P: AsRef<Path>>(self, path: P) -> Self {
+        match self {
+            ReadError::File(..) => self,
+            e => ReadError::File(path.as_ref().into(), Box::new(e)),
+        }
+    }
+
+    /// The file the error happened in, if known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ReadError::File(path, _) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The error without the file it happened in
+    pub fn inner(&self) -> &ReadError {
+        match self {
+            ReadError::File(_, e) => e.inner(),
+            e => e,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -329,19 +763,20 @@ pub struct DxvkStateCache {
 impl FromReader for DxvkStateCache {
     type Error = ReadError;
 
-    fn from_reader<R: Read>(mut reader: R) -> Result<Self, Self::Error> {
+    #[inline(always)]
+    fn from_reader<R: Read>(reader: R) -> Result<Self, Self::Error> {
+        Self::from_reader_with(reader, &ReadOptions::default())
+    }
+}
+
+impl DxvkStateCache {
+    pub fn from_reader_with<R: Read>(mut reader: R, options: &ReadOptions) -> Result<Self, ReadError> {
         let mut entries: HashSet<EntryWrapper> = HashSet::new();
-        let header = DxvkStateCacheHeader::from_reader(&mut reader)?;
-        let mut try_read_entry = || {
-            match DxvkStateCacheEntry::from_reader(&mut reader, &header) {
-                Ok(v) => Ok(Some(v)),
-                Err(EntryError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
-                Err(e) => Err(e),
-            }
-        };
-        while let Some(e) = try_read_entry()?.map(EntryWrapper::from) {
-            if !entries.insert(e) {
-                return Err(ReadError::DuplicateEntry);
+        let header = DxvkStateCacheHeader::from_reader_with(&mut reader, options)?;
+        let mut reader = EntryReader::new(reader, header, options.limits);
+        while let Some(e) = reader.next_entry().map_err(|e| ReadError::ReadEntry(reader.position(), e))? {
+            if !entries.insert(EntryWrapper::from(e)) {
+                return Err(ReadError::DuplicateEntry(reader.position()));
             }
         }
         Ok(DxvkStateCache {
@@ -349,12 +784,10 @@ impl FromReader for DxvkStateCache {
             entries: entries,
         })
     }
-}
 
-impl DxvkStateCache {
     pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        if self.entries.len() < 1 {
-            return Err(io::Error::new(io::ErrorKind::Other, "No entries to write"));
+        if self.entries.is_empty() {
+            return Err(io::Error::other("No entries to write"));
         }
         self.header.write_to(&mut writer)?;
         let edition = self.header.edition();
@@ -368,43 +801,67 @@ impl DxvkStateCache {
         self.entries.iter().map(|v| &v.0)
     }
 
-    pub fn from_file<P: AsRef<Path>>(p: P) -> Result<Self, ReadError> {
-        fs::OpenOptions::new()
-            .read(true)
-            .open(p)
-            .map(io::BufReader::new)
+    /// Reads the cache at `p`, decompressing it if needed
+    pub fn from_file<P: AsRef<Path>>(p: P, options: &ReadOptions) -> Result<Self, ReadError> {
+        compress::open(&p)
             .map_err(ReadError::from)
-            .and_then(Self::from_reader)
+            .and_then(|r| Self::from_reader_with(r, options))
+            .map_err(|e| e.in_file(p))
     }
 
-    pub fn append_from<R: Read>(&mut self, mut reader: R) -> Result<usize, ReadError> {
-        let header = DxvkStateCacheHeader::from_reader(&mut reader)?;
-        if header.version != self.header.version {
-            return Err(ReadError::VersionMismatch {
-                found: header.version,
-                expected: self.header.version,
-            });
-        }
-        let mut try_read_entry = || {
-            match DxvkStateCacheEntry::from_reader(&mut reader, &header) {
-                Ok(v) => Ok(Some(v)),
-                Err(EntryError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
-                Err(e) => Err(e),
-            }
-        };
+    pub fn append_from<R: Read>(&mut self, mut reader: R, options: &ReadOptions) -> Result<usize, ReadError> {
+        let header = DxvkStateCacheHeader::from_reader_with(&mut reader, options)?;
+        self.header.check_compatible(&header)?;
+        let mut reader = EntryReader::new(reader, header, options.limits);
         let mut new_count = 0usize;
-        while let Some(e) = try_read_entry()?.map(EntryWrapper::from) {
-            if self.entries.insert(e) {
-                new_count = new_count + 1;
+        while let Some(e) = reader.next_entry().map_err(|e| ReadError::ReadEntry(reader.position(), e))? {
+            if self.entries.insert(EntryWrapper::from(e)) {
+                new_count += 1;
             }
         }
         Ok(new_count)
     }
+
+
+    fn with_entries<'a, It>(&self, entries: It) -> DxvkStateCache
+    where
+        It: Iterator<Item=&'a EntryWrapper>,
+    {
+        DxvkStateCache {
+            header: self.header,
+            entries: entries.cloned().collect(),
+        }
+    }
+
+    /// Entries present in either cache
+    pub fn merge(&self, other: &DxvkStateCache) -> Result<DxvkStateCache, ReadError> {
+        self.header.check_compatible(&other.header)?;
+        Ok(self.with_entries(self.entries.union(&other.entries)))
+    }
+
+    /// Entries present in both caches
+    pub fn intersect(&self, other: &DxvkStateCache) -> Result<DxvkStateCache, ReadError> {
+        self.header.check_compatible(&other.header)?;
+        Ok(self.with_entries(self.entries.intersection(&other.entries)))
+    }
+
+    /// Entries present in this cache but not in `other`
+    pub fn difference(&self, other: &DxvkStateCache) -> Result<DxvkStateCache, ReadError> {
+        self.header.check_compatible(&other.header)?;
+        Ok(self.with_entries(self.entries.difference(&other.entries)))
+    }
 }
 
 #[repr(transparent)]
 pub struct HashDisplay<'a>(&'a [u8; HASH_SIZE]);
 
+impl<'a> HashDisplay<'a> {
+    #[inline(always)]
+    pub fn new(hash: &'a Sha1Hash) -> Self {
+        HashDisplay(hash)
+    }
+}
+
 impl<'a> fmt::Display for HashDisplay<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         const HASH_STR_SIZE: usize = HASH_SIZE * 2;
@@ -419,3 +876,42 @@ impl<'a> fmt::Display for HashDisplay<'a> {
         write!(f, "{}", s)
     }
 }
+
+/// Parses a hash from the 40 hexadecimal digits [`HashDisplay`] produces
+pub fn parse_hash(s: &str) -> Option<Sha1Hash> {
+    if s.len() != HASH_SIZE * 2 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut hash = [0; HASH_SIZE];
+    for (i, b) in hash.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// A hash given as all of its hexadecimal digits, or as the first few of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashPrefix(String);
+
+impl FromStr for HashPrefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() > HASH_SIZE * 2 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("invalid hash \"{}\", expected up to {} hexadecimal digits", s, HASH_SIZE * 2));
+        }
+        Ok(HashPrefix(s.to_ascii_lowercase()))
+    }
+}
+
+impl fmt::Display for HashPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl HashPrefix {
+    pub fn matches(&self, hash: &Sha1Hash) -> bool {
+        HashDisplay::new(hash).to_string().starts_with(&self.0)
+    }
+}
@@ -1,33 +1,302 @@
 use std::{
+    error::Error as StdError,
     io,
-    num::NonZeroU32,
+    path::{Path, PathBuf},
 };
 use crate::{
-    dxvk::{HeaderError, EntryError},
+    bundle::BundleError,
+    client::ClientError,
+    dxvk::{DxvkStateCacheHeader, HeaderError, EntryError, ReadError},
+    locate::LocateError,
+    provenance::ProvenanceError,
+    resolve::ResolveError,
+    server::ServeError,
+    store::StoreError,
 };
+#[cfg(target_os = "linux")]
+use crate::watch::WatchError;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("{0}")]
     Io(#[from] io::Error),
-    #[error("State cache version mismatch: expected v{expected}, found v{found}")]
-    VersionMismatch {
-        expected: NonZeroU32,
-        found: NonZeroU32,
-    },
+    /// Reading an input failed, see [`ReadError`] for the file and position
+    #[error("{0}")]
+    Read(#[from] ReadError),
     #[error("No valid state cache entries found")]
     NoEntriesFound,
-    #[error("Error reading header: {0}")]
-    ReadHeader(#[from] HeaderError),
-    #[error("Error reading entry: {0}")]
-    ReadEntry(#[from] EntryError),
+    #[error(
+        "Append target header mismatch: target is v{} with entry size {}, inputs are v{} with entry size {}",
+        .target.version, .target.entry_size, .found.version, .found.entry_size
+    )]
+    AppendHeaderMismatch {
+        target: DxvkStateCacheHeader,
+        found: DxvkStateCacheHeader,
+    },
+    #[error("Cannot append to {}: file contains invalid or truncated entries", .0.display())]
+    AppendTargetInvalid(PathBuf),
+    #[error("Cannot append to {}: compressed files can only be rewritten", .0.display())]
+    AppendCompressed(PathBuf),
+    #[error("Cannot append to {}: big-endian files can only be rewritten", .0.display())]
+    AppendBigEndian(PathBuf),
+    #[error("{0}")]
+    Provenance(#[from] ProvenanceError),
+    #[error("{prefix} matches several entries: {}", .matches.join(", "))]
+    AmbiguousHash {
+        prefix:  String,
+        matches: Vec<String>,
+    },
+    #[error("No entry {prefix} in {}", .file.display())]
+    NoMatchingHash {
+        prefix: String,
+        file:   PathBuf,
+    },
+    #[error("{} of {total} inputs were skipped", .skipped.len())]
+    PartialSuccess {
+        skipped: Vec<SkippedInput>,
+        total: usize,
+    },
+}
+
+/// An input left out with `--keep-going`, and why
+#[derive(Debug)]
+pub struct SkippedInput {
+    pub path:    PathBuf,
+    pub kind:    ErrorKind,
+    pub message: String,
+}
+
+impl From<HeaderError> for Error {
+    #[inline]
+    fn from(e: HeaderError) -> Self {
+        Error::Read(e.into())
+    }
 }
 
 impl Error {
-    pub const fn version_mismatch(expected: NonZeroU32, found: NonZeroU32) -> Self {
-        Error::VersionMismatch {
-            expected: expected,
-            found: found,
+    pub const fn partial_success(skipped: Vec<SkippedInput>, total: usize) -> Self {
+        Error::PartialSuccess {
+            skipped: skipped,
+            total: total,
+        }
+    }
+}
+
+/// Classes of failure, each with its own exit status so scripts can tell them apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Anything not covered by the other kinds
+    Other,
+    /// Invalid command line arguments
+    Usage,
+    /// Some inputs were skipped with `--keep-going`, the rest succeeded
+    PartialSuccess,
+    NoEntries,
+    VersionMismatch,
+    /// An input isn't a valid state cache, or one of its entries is damaged
+    CorruptInput,
+    /// Reading or writing a file failed
+    Io,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 7] = [
+        ErrorKind::Other,
+        ErrorKind::Usage,
+        ErrorKind::PartialSuccess,
+        ErrorKind::NoEntries,
+        ErrorKind::VersionMismatch,
+        ErrorKind::CorruptInput,
+        ErrorKind::Io,
+    ];
+
+    pub const fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::PartialSuccess => 3,
+            ErrorKind::NoEntries => 4,
+            ErrorKind::VersionMismatch => 5,
+            ErrorKind::CorruptInput => 6,
+            ErrorKind::Io => 7,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Other => "other",
+            ErrorKind::Usage => "usage",
+            ErrorKind::PartialSuccess => "partial_success",
+            ErrorKind::NoEntries => "no_entries",
+            ErrorKind::VersionMismatch => "version_mismatch",
+            ErrorKind::CorruptInput => "corrupt_input",
+            ErrorKind::Io => "io",
+        }
+    }
+
+    /// Running out of data means a file was cut short, rather than that reading it failed
+    fn of_io(e: &io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData => ErrorKind::CorruptInput,
+            _ => ErrorKind::Io,
+        }
+    }
+
+    fn of_header(e: &HeaderError) -> Self {
+        match e {
+            HeaderError::Io(e) => Self::of_io(e),
+            _ => ErrorKind::CorruptInput,
+        }
+    }
+
+    fn of_entry(e: &EntryError) -> Self {
+        match e {
+            EntryError::Io(e) => Self::of_io(e),
+            _ => ErrorKind::CorruptInput,
+        }
+    }
+
+    fn of_read(e: &ReadError) -> Self {
+        match e {
+            ReadError::Io(e) => Self::of_io(e),
+            ReadError::ReadHeader(e) => Self::of_header(e),
+            ReadError::ReadEntry(_, e) => Self::of_entry(e),
+            ReadError::DuplicateEntry(..) => ErrorKind::CorruptInput,
+            ReadError::VersionMismatch { .. } | ReadError::EntrySizeMismatch { .. } => ErrorKind::VersionMismatch,
+            ReadError::File(_, e) => Self::of_read(e),
+        }
+    }
+
+    /// Classifies the errors of this crate, along with I/O errors
+    pub fn of(e: &(dyn StdError + 'static)) -> Self {
+        #[cfg(target_os = "linux")]
+        if let Some(e) = e.downcast_ref::<WatchError>() {
+            return match e {
+                WatchError::Io(_, e) | WatchError::Inotify(e) => Self::of_io(e),
+                WatchError::Read(e) => Self::of_read(e),
+                WatchError::Store(e) => Self::of(e),
+            };
+        }
+        if let Some(e) = e.downcast_ref::<Error>() {
+            match e {
+                Error::Io(e) => Self::of_io(e),
+                Error::Read(e) => Self::of_read(e),
+                Error::AppendHeaderMismatch { .. } => ErrorKind::VersionMismatch,
+                Error::NoEntriesFound => ErrorKind::NoEntries,
+                Error::AppendTargetInvalid(..) => ErrorKind::CorruptInput,
+                Error::AppendCompressed(..) | Error::AppendBigEndian(..) => ErrorKind::Other,
+                Error::Provenance(ProvenanceError::Io(_, e)) => Self::of_io(e),
+                Error::Provenance(ProvenanceError::Invalid(..)) => ErrorKind::CorruptInput,
+                Error::AmbiguousHash { .. } | Error::NoMatchingHash { .. } => ErrorKind::Usage,
+                Error::PartialSuccess { .. } => ErrorKind::PartialSuccess,
+            }
+        } else if let Some(e) = e.downcast_ref::<ReadError>() {
+            Self::of_read(e)
+        } else if let Some(e) = e.downcast_ref::<HeaderError>() {
+            Self::of_header(e)
+        } else if let Some(e) = e.downcast_ref::<StoreError>() {
+            match e {
+                StoreError::Io(_, e) => Self::of_io(e),
+                StoreError::Read(e) => Self::of_read(e),
+                StoreError::Object(_, e) => Self::of_entry(e),
+                StoreError::ObjectMismatch(..) | StoreError::InvalidManifest(..) => ErrorKind::CorruptInput,
+                _ => ErrorKind::Other,
+            }
+        } else if let Some(e) = e.downcast_ref::<BundleError>() {
+            match e.inner() {
+                BundleError::Io(e) => Self::of_io(e),
+                BundleError::Read { error, .. } => Self::of_read(error),
+                _ => ErrorKind::CorruptInput,
+            }
+        } else if let Some(e) = e.downcast_ref::<ClientError>() {
+            match e {
+                ClientError::Io(_, e) => Self::of_io(e),
+                ClientError::Read(_, e) => Self::of_read(e),
+                ClientError::Status { status: 409, .. } => ErrorKind::VersionMismatch,
+                ClientError::Status { .. } => ErrorKind::Other,
+                // The server couldn't be reached, or the connection broke
+                ClientError::Transport(..) => ErrorKind::Io,
+                ClientError::Response(..) => ErrorKind::CorruptInput,
+            }
+        } else if let Some(e) = e.downcast_ref::<ServeError>() {
+            match e {
+                ServeError::Bind(_, e) => e.downcast_ref::<io::Error>().map_or(ErrorKind::Other, Self::of_io),
+                ServeError::Io(e) => Self::of_io(e),
+            }
+        } else if let Some(e) = e.downcast_ref::<ProvenanceError>() {
+            match e {
+                ProvenanceError::Io(_, e) => Self::of_io(e),
+                ProvenanceError::Invalid(..) => ErrorKind::CorruptInput,
+            }
+        } else if let Some(e) = e.downcast_ref::<io::Error>() {
+            Self::of_io(e)
+        } else if let Some(e) = e.downcast_ref::<LocateError>() {
+            match e {
+                LocateError::Io(_, e) => Self::of_io(e),
+                LocateError::Vdf(..) | LocateError::Yaml(..) | LocateError::Json(..) => ErrorKind::CorruptInput,
+            }
+        } else if let Some(e) = e.downcast_ref::<ResolveError>() {
+            match e {
+                ResolveError::Io(_, e) => Self::of_io(e),
+                // The executable was given on the command line
+                ResolveError::NoFileName(..) => ErrorKind::Usage,
+                // The game's environment points somewhere that can't be found
+                ResolveError::UnmappablePath { .. } => ErrorKind::Other,
+            }
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+/// The file an error of this crate is about, if it names one
+pub fn error_file<'a>(e: &'a (dyn StdError + 'static)) -> Option<&'a Path> {
+    #[cfg(target_os = "linux")]
+    if let Some(e) = e.downcast_ref::<WatchError>() {
+        return match e {
+            WatchError::Io(path, _) => Some(path),
+            WatchError::Inotify(..) => None,
+            WatchError::Read(e) => e.path(),
+            WatchError::Store(e) => error_file(e),
+        };
+    }
+    if let Some(e) = e.downcast_ref::<Error>() {
+        match e {
+            Error::Read(e) => e.path(),
+            Error::AppendTargetInvalid(path)
+            | Error::AppendCompressed(path)
+            | Error::AppendBigEndian(path)
+            | Error::NoMatchingHash { file: path, .. } => Some(path),
+            Error::Provenance(ProvenanceError::Io(path, _) | ProvenanceError::Invalid(path, _)) => Some(path),
+            _ => None,
+        }
+    } else if let Some(e) = e.downcast_ref::<ReadError>() {
+        e.path()
+    } else if let Some(e) = e.downcast_ref::<StoreError>() {
+        match e {
+            StoreError::Read(e) => e.path(),
+            StoreError::Io(path, _)
+            | StoreError::Object(path, _)
+            | StoreError::ObjectMismatch(path)
+            | StoreError::InvalidManifest(path, _) => Some(path),
+            _ => None,
+        }
+    } else if let Some(e) = e.downcast_ref::<BundleError>() {
+        e.path()
+    } else if let Some(ClientError::Io(path, _)) = e.downcast_ref::<ClientError>() {
+        Some(path)
+    } else if let Some(ProvenanceError::Io(path, _) | ProvenanceError::Invalid(path, _)) = e.downcast_ref() {
+        Some(path)
+    } else if let Some(e) = e.downcast_ref::<LocateError>() {
+        match e {
+            LocateError::Io(path, _)
+            | LocateError::Vdf(path, _)
+            | LocateError::Yaml(path, _)
+            | LocateError::Json(path, _) => Some(path),
         }
+    } else if let Some(ResolveError::Io(path, _) | ResolveError::NoFileName(path)) = e.downcast_ref() {
+        Some(path)
+    } else {
+        None
     }
 }
@@ -0,0 +1,171 @@
+use std::{
+    io::{
+        self,
+        Write,
+    },
+    num::NonZeroU32,
+    ops::RangeInclusive,
+};
+use crate::dxvk::*;
+
+/// Stage masks used when none are given: a vertex + fragment graphics pipeline, and a compute
+/// pipeline
+pub const DEFAULT_STAGE_MASKS: [u8; 2] = [0x11, 0x20];
+
+/// Smallest entry payload the generator produces. Every entry starts with its index, which keeps
+/// the generated entries unique without having to remember all of them.
+pub const MIN_DATA_SIZE: usize = 8;
+
+/// Parameters for a synthetic state cache
+#[derive(Debug, Clone)]
+pub struct GenerateConfig {
+    pub version:     NonZeroU32,
+    pub count:       usize,
+    /// Range of entry payload sizes, not counting the hash or entry header. Legacy caches have a
+    /// fixed entry size, so this has to be a single size for those.
+    pub data_sizes:  RangeInclusive<usize>,
+    /// Stage masks to pick from for each entry. Ignored for legacy caches.
+    pub stage_masks: Vec<u8>,
+    pub seed:        u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GenerateError {
+    #[error("Entry sizes must be at least {} bytes", MIN_DATA_SIZE)]
+    SizeTooSmall,
+    #[error("Entry size of {0} bytes is too large for v{1}")]
+    SizeTooLarge(usize, NonZeroU32),
+    #[error("Invalid entry size range {}-{}", .0.start(), .0.end())]
+    InvalidSizeRange(RangeInclusive<usize>),
+    #[error("Legacy caches (v{0}) have a fixed entry size, but a range was given")]
+    LegacySizeRange(NonZeroU32),
+    #[error("At least one stage mask is required")]
+    NoStageMasks,
+}
+
+impl GenerateConfig {
+    pub fn validate(&self) -> Result<(), GenerateError> {
+        let (min, max) = (*self.data_sizes.start(), *self.data_sizes.end());
+        if min > max {
+            return Err(GenerateError::InvalidSizeRange(self.data_sizes.clone()));
+        }
+        if min < MIN_DATA_SIZE {
+            return Err(GenerateError::SizeTooSmall);
+        }
+        let header = self.header();
+        match header.edition() {
+            DxvkStateCacheEdition::Legacy => {
+                if min != max {
+                    return Err(GenerateError::LegacySizeRange(self.version));
+                }
+                if !header.entry_size_bounds().contains(&header.entry_size) {
+                    return Err(GenerateError::SizeTooLarge(max, self.version));
+                }
+            },
+            DxvkStateCacheEdition::Standard => {
+                if max > MAX_ENTRY_SIZE as usize {
+                    return Err(GenerateError::SizeTooLarge(max, self.version));
+                }
+                if self.stage_masks.is_empty() {
+                    return Err(GenerateError::NoStageMasks);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    pub fn header(&self) -> DxvkStateCacheHeader {
+        let entry_size = match DxvkStateCacheHeader::new(self.version, 0).edition() {
+            DxvkStateCacheEdition::Legacy => (self.data_sizes.start() + HASH_SIZE) as u32,
+            DxvkStateCacheEdition::Standard => 0,
+        };
+        DxvkStateCacheHeader::new(self.version, entry_size)
+    }
+
+    /// Returns an iterator over the generated entries, after validating the configuration
+    pub fn entries(&self) -> Result<Entries<'_>, GenerateError> {
+        self.validate()?;
+        Ok(Entries {
+            config: self,
+            edition: self.header().edition(),
+            rng: SplitMix64(self.seed),
+            index: 0,
+        })
+    }
+
+    /// Streams the generated cache to `writer`, returning the number of entries written
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<usize, io::Error> {
+        let entries = self.entries()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let header = self.header();
+        header.write_to(&mut writer)?;
+        let edition = header.edition();
+        for entry in entries {
+            entry.write_to(&mut writer, edition)?;
+        }
+        writer.flush()?;
+        Ok(self.count)
+    }
+}
+
+pub struct Entries<'a> {
+    config:  &'a GenerateConfig,
+    edition: DxvkStateCacheEdition,
+    rng:     SplitMix64,
+    index:   usize,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = DxvkStateCacheEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.config.count {
+            return None;
+        }
+        let (min, max) = (*self.config.data_sizes.start(), *self.config.data_sizes.end());
+        let size = min + self.rng.below((max - min) as u64 + 1) as usize;
+        let mut data = vec![0u8; size];
+        data[..8].copy_from_slice(&(self.index as u64).to_le_bytes());
+        self.rng.fill(&mut data[8..]);
+        let masks = &self.config.stage_masks;
+        let stage_mask = if masks.is_empty() {
+            0
+        } else {
+            masks[self.rng.below(masks.len() as u64) as usize]
+        };
+        self.index += 1;
+        Some(DxvkStateCacheEntry::new(data, stage_mask, self.edition))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.config.count - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for Entries<'a> {}
+
+/// Small deterministic PRNG, so the same seed always produces the same cache
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    #[inline]
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let v = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&v[..chunk.len()]);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+pub mod append;
+pub mod bundle;
+#[cfg(feature = "browse")]
+pub mod browse;
+pub mod client;
+pub mod compress;
+pub mod dxvk;
+pub mod error;
+pub mod generate;
+pub mod locate;
+pub mod provenance;
+pub mod read;
+pub mod report;
+pub mod resolve;
+pub mod server;
+pub mod show;
+pub mod similarity;
+pub mod store;
+#[cfg(target_os = "linux")]
+pub mod watch;
@@ -0,0 +1,79 @@
+//! Bottles, where every bottle is a Wine prefix described by its `bottle.yml`
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use serde_yaml::Value;
+use super::{
+    FoundCache,
+    GameLocation,
+    Launcher,
+    LocateError,
+    STATE_CACHE_PATH_VAR,
+};
+
+/// Directories holding bottles, relative to the home directory
+const ROOTS: [&str; 2] = [
+    ".local/share/bottles/bottles",
+    ".var/app/com.usebottles.bottles/data/bottles/bottles",
+];
+
+pub fn default_roots() -> Vec<PathBuf> {
+    super::home_dirs(&ROOTS)
+}
+
+/// Locations of a single bottle. Programs added to the bottle come first, so caches next to their
+/// executables are attributed to them, and anything else in the prefix to the bottle itself.
+fn bottle_locations(dir: &Path, doc: &Value) -> Vec<GameLocation> {
+    let name = doc["Name"].as_str()
+        .map(String::from)
+        .unwrap_or_else(|| dir.file_name().unwrap().to_string_lossy().into_owned());
+    let prefix = match doc["Path"].as_str() {
+        Some(path) if doc["Custom_Path"].as_bool() == Some(true) => PathBuf::from(path),
+        _ => dir.to_path_buf(),
+    };
+    let mut locations = Vec::new();
+    let programs = doc["External_Programs"].as_mapping().into_iter().flatten();
+    for (_, program) in programs {
+        let folder = program["folder"].as_str()
+            .map(PathBuf::from)
+            .or_else(|| program["path"].as_str().and_then(|p| Path::new(p).parent()).map(Path::to_path_buf));
+        locations.push(GameLocation {
+            launcher: Launcher::Bottles,
+            game_id: name.clone(),
+            game_name: program["name"].as_str().map(From::from),
+            dirs: folder.into_iter().collect(),
+        });
+    }
+    let mut dirs = Vec::new();
+    if let Some(path) = doc["Environment_Variables"][STATE_CACHE_PATH_VAR].as_str() {
+        dirs.extend(super::resolve_wine_path(path, Some(&prefix)));
+    }
+    dirs.push(prefix);
+    locations.push(GameLocation {
+        launcher: Launcher::Bottles,
+        game_id: name,
+        game_name: None,
+        dirs: dirs,
+    });
+    locations
+}
+
+/// Finds the state caches in every bottle below `root`
+pub fn find_caches(root: &Path) -> Result<Vec<FoundCache>, LocateError> {
+    let mut bottles: Vec<_> = fs::read_dir(root)
+        .map_err(|e| LocateError::Io(root.into(), e))?
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| e.path())
+        .collect();
+    bottles.sort();
+    let mut games = Vec::new();
+    for dir in bottles.iter() {
+        if let Some(doc) = super::skip_malformed(super::read_yaml(&dir.join("bottle.yml")))? {
+            games.extend(bottle_locations(dir, &doc));
+        }
+    }
+    Ok(super::find_game_caches(games))
+}
@@ -0,0 +1,111 @@
+//! Heroic Games Launcher, which keeps per-game settings in `GamesConfig/<appName>.json` and
+//! the installed games of each store in separate files
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use serde_json::Value;
+use super::{
+    FoundCache,
+    GameLocation,
+    Launcher,
+    LocateError,
+    STATE_CACHE_PATH_VAR,
+};
+
+/// Heroic's configuration directories, relative to the home directory
+const ROOTS: [&str; 2] = [
+    ".config/heroic",
+    ".var/app/com.heroicgameslauncher.hgl/config/heroic",
+];
+
+pub fn default_roots() -> Vec<PathBuf> {
+    super::home_dirs(&ROOTS)
+}
+
+#[derive(Debug, Default)]
+struct Game {
+    title:        Option<String>,
+    install_path: Option<PathBuf>,
+    prefix:       Option<PathBuf>,
+    cache_path:   Option<String>,
+}
+
+/// Adds the games installed from the Epic and GOG stores
+fn read_installed(root: &Path, games: &mut BTreeMap<String, Game>) -> Result<(), LocateError> {
+    let legendary = root.join("legendaryConfig/legendary/installed.json");
+    if let Some(Value::Object(installed)) = super::skip_malformed(super::read_json(&legendary))? {
+        for (app_name, info) in installed {
+            let game = games.entry(app_name).or_default();
+            game.title = info["title"].as_str().map(From::from);
+            game.install_path = info["install_path"].as_str().map(PathBuf::from);
+        }
+    }
+    let gog = root.join("gog_store/installed.json");
+    if let Some(doc) = super::skip_malformed(super::read_json(&gog))? {
+        for info in doc["installed"].as_array().into_iter().flatten() {
+            if let Some(app_name) = info["appName"].as_str() {
+                let game = games.entry(app_name.into()).or_default();
+                game.install_path = info["install_path"].as_str().map(PathBuf::from);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the Wine prefix and environment overrides from each game's settings
+fn read_games_config(root: &Path, games: &mut BTreeMap<String, Game>) -> Result<(), LocateError> {
+    let dir = root.join("GamesConfig");
+    let entries = match fs::read_dir(&dir) {
+        Ok(v) => v,
+        Err(..) => return Ok(()),
+    };
+    for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let app_name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let doc = match super::skip_malformed(super::read_json(&path))? {
+            Some(v) => v,
+            None => continue,
+        };
+        let config = &doc[&app_name];
+        if config.is_null() {
+            continue;
+        }
+        let game = games.entry(app_name).or_default();
+        game.prefix = config["winePrefix"].as_str().map(PathBuf::from);
+        // Heroic spells the key this way
+        game.cache_path = config["enviromentOptions"].as_array()
+            .into_iter()
+            .flatten()
+            .find(|opt| opt["key"].as_str() == Some(STATE_CACHE_PATH_VAR))
+            .and_then(|opt| opt["value"].as_str())
+            .map(From::from);
+    }
+    Ok(())
+}
+
+/// Finds the state caches of the games Heroic, configured in `root`, knows about
+pub fn find_caches(root: &Path) -> Result<Vec<FoundCache>, LocateError> {
+    let mut games = BTreeMap::new();
+    read_installed(root, &mut games)?;
+    read_games_config(root, &mut games)?;
+    let games = games.into_iter().map(|(app_name, game)| {
+        let mut dirs = Vec::new();
+        if let Some(path) = game.cache_path.as_ref() {
+            dirs.extend(super::resolve_wine_path(path, game.prefix.as_deref()));
+        }
+        dirs.extend(game.install_path);
+        dirs.extend(game.prefix);
+        GameLocation {
+            launcher: Launcher::Heroic,
+            game_id: app_name,
+            game_name: game.title,
+            dirs: dirs,
+        }
+    });
+    Ok(super::find_game_caches(games))
+}
@@ -0,0 +1,77 @@
+//! Lutris games, configured by one YAML file per game
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use serde_yaml::Value;
+use super::{
+    FoundCache,
+    GameLocation,
+    Launcher,
+    LocateError,
+    STATE_CACHE_PATH_VAR,
+};
+
+/// Directories holding Lutris' game configurations, relative to the home directory
+const ROOTS: [&str; 4] = [
+    ".config/lutris/games",
+    ".local/share/lutris/games",
+    ".var/app/net.lutris.Lutris/config/lutris/games",
+    ".var/app/net.lutris.Lutris/data/lutris/games",
+];
+
+pub fn default_roots() -> Vec<PathBuf> {
+    super::home_dirs(&ROOTS)
+}
+
+/// Game configurations are named `<slug>-<timestamp>.yml`, the slug identifies the game
+fn game_slug(stem: &str) -> &str {
+    match stem.rsplit_once('-') {
+        Some((slug, ts)) if !slug.is_empty() && ts.bytes().all(|b| b.is_ascii_digit()) => slug,
+        _ => stem,
+    }
+}
+
+fn game_location(stem: &str, doc: &Value) -> GameLocation {
+    let game = &doc["game"];
+    let prefix = game["prefix"].as_str().map(PathBuf::from);
+    let mut dirs = Vec::new();
+    if let Some(path) = doc["system"]["env"][STATE_CACHE_PATH_VAR].as_str() {
+        dirs.extend(super::resolve_wine_path(path, prefix.as_deref()));
+    }
+    if let Some(exe) = game["exe"].as_str() {
+        let exe = match prefix.as_ref() {
+            Some(prefix) => prefix.join(exe),
+            None => PathBuf::from(exe),
+        };
+        dirs.extend(exe.parent().map(Path::to_path_buf));
+    }
+    dirs.extend(game["working_dir"].as_str().map(PathBuf::from));
+    dirs.extend(prefix);
+    GameLocation {
+        launcher: Launcher::Lutris,
+        game_id: game_slug(stem).into(),
+        game_name: doc["name"].as_str().map(From::from),
+        dirs: dirs,
+    }
+}
+
+/// Finds the state caches of the games configured in `root`
+pub fn find_caches(root: &Path) -> Result<Vec<FoundCache>, LocateError> {
+    let mut configs: Vec<_> = fs::read_dir(root)
+        .map_err(|e| LocateError::Io(root.into(), e))?
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "yml"))
+        .collect();
+    configs.sort();
+    let mut games = Vec::new();
+    for path in configs.iter() {
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        if let Some(doc) = super::skip_malformed(super::read_yaml(path))? {
+            games.push(game_location(&stem, &doc));
+        }
+    }
+    Ok(super::find_game_caches(games))
+}
@@ -0,0 +1,258 @@
+//! Discovery of the state caches games write, by looking through launchers' install locations
+
+pub mod bottles;
+pub mod heroic;
+pub mod lutris;
+pub mod steam;
+pub mod vdf;
+
+use std::{
+    collections::HashSet,
+    env,
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use log::{debug, warn};
+use self::vdf::VdfError;
+
+pub const CACHE_EXTENSION: &str = "dxvk-cache";
+/// Environment variable pointing DXVK at the directory to keep its state cache in
+pub const STATE_CACHE_PATH_VAR: &str = "DXVK_STATE_CACHE_PATH";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Launcher {
+    Steam,
+    Lutris,
+    Heroic,
+    Bottles,
+}
+
+impl Launcher {
+    pub const ALL: [Launcher; 4] = [
+        Launcher::Steam,
+        Launcher::Lutris,
+        Launcher::Heroic,
+        Launcher::Bottles,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Launcher::Steam => "steam",
+            Launcher::Lutris => "lutris",
+            Launcher::Heroic => "heroic",
+            Launcher::Bottles => "bottles",
+        }
+    }
+
+    /// Where this launcher usually keeps its data in the current user's home directory
+    pub fn default_roots(self) -> Vec<PathBuf> {
+        match self {
+            Launcher::Steam => steam::default_roots(),
+            Launcher::Lutris => lutris::default_roots(),
+            Launcher::Heroic => heroic::default_roots(),
+            Launcher::Bottles => bottles::default_roots(),
+        }
+    }
+
+    pub fn find_caches(self, root: &Path) -> Result<Vec<FoundCache>, LocateError> {
+        match self {
+            Launcher::Steam => steam::find_caches(root),
+            Launcher::Lutris => lutris::find_caches(root),
+            Launcher::Heroic => heroic::find_caches(root),
+            Launcher::Bottles => bottles::find_caches(root),
+        }
+    }
+}
+
+impl fmt::Display for Launcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Launcher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Launcher::ALL.iter()
+            .copied()
+            .find(|l| l.name() == s)
+            .ok_or_else(|| format!("unknown launcher \"{}\"", s))
+    }
+}
+
+/// A state cache found on disk, along with the game it belongs to
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FoundCache {
+    pub launcher:  Launcher,
+    /// The launcher's identifier for the game, e.g. the Steam appid
+    pub game_id:   String,
+    pub game_name: Option<String>,
+    pub path:      PathBuf,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LocateError {
+    #[error("{}: {}", .0.display(), .1)]
+    Io(PathBuf, io::Error),
+    #[error("{}: {}", .0.display(), .1)]
+    Vdf(PathBuf, VdfError),
+    #[error("{}: {}", .0.display(), .1)]
+    Yaml(PathBuf, serde_yaml::Error),
+    #[error("{}: {}", .0.display(), .1)]
+    Json(PathBuf, serde_json::Error),
+}
+
+/// A game known to a launcher, and the directories its state cache could be in
+#[derive(Debug, Clone)]
+pub(crate) struct GameLocation {
+    pub launcher:  Launcher,
+    pub game_id:   String,
+    pub game_name: Option<String>,
+    pub dirs:      Vec<PathBuf>,
+}
+
+impl GameLocation {
+    /// Searches the game's directories. A directory often contains another one, e.g. a game
+    /// installed inside its Wine prefix, so each cache is only reported once.
+    pub fn find_caches(&self, seen: &mut HashSet<PathBuf>) -> Vec<FoundCache> {
+        self.dirs.iter()
+            .flat_map(|dir| find_cache_files(dir))
+            .filter(|path| seen.insert(path.clone()))
+            .map(|path| FoundCache {
+                launcher: self.launcher,
+                game_id: self.game_id.clone(),
+                game_name: self.game_name.clone(),
+                path: path,
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn find_game_caches<It>(games: It) -> Vec<FoundCache>
+where
+    It: IntoIterator<Item=GameLocation>,
+{
+    let mut seen = HashSet::new();
+    let mut found: Vec<_> = games.into_iter()
+        .flat_map(|game| game.find_caches(&mut seen))
+        .collect();
+    found.sort();
+    found
+}
+
+/// Reads and parses a YAML file, returning `None` if it doesn't exist
+pub(crate) fn read_yaml(path: &Path) -> Result<Option<serde_yaml::Value>, LocateError> {
+    read_optional(path)?
+        .map(|s| serde_yaml::from_str(&s).map_err(|e| LocateError::Yaml(path.into(), e)))
+        .transpose()
+}
+
+/// Reads and parses a JSON file, returning `None` if it doesn't exist
+pub(crate) fn read_json(path: &Path) -> Result<Option<serde_json::Value>, LocateError> {
+    read_optional(path)?
+        .map(|s| serde_json::from_str(&s).map_err(|e| LocateError::Json(path.into(), e)))
+        .transpose()
+}
+
+/// Turns a config file that fails to parse into a warning, so one broken game doesn't hide the
+/// launcher's other games
+pub(crate) fn skip_malformed<T>(
+    result: Result<Option<T>, LocateError>,
+) -> Result<Option<T>, LocateError> {
+    match result {
+        Err(e @ LocateError::Vdf(..))
+        | Err(e @ LocateError::Yaml(..))
+        | Err(e @ LocateError::Json(..)) => {
+            warn!("Skipping {}", e);
+            Ok(None)
+        },
+        result => result,
+    }
+}
+
+/// Keeps the directories that exist, dropping any that resolve to one seen before
+pub(crate) fn dedup_dirs<It: IntoIterator<Item=PathBuf>>(dirs: It) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    dirs.into_iter()
+        .filter(|p| fs::canonicalize(p).is_ok_and(|p| seen.insert(p)))
+        .collect()
+}
+
+/// Resolves a path from a game's configuration to a host path. Windows paths are mapped into the
+/// drives of the game's Wine prefix, if it has one.
+pub(crate) fn resolve_wine_path(path: &str, prefix: Option<&Path>) -> Option<PathBuf> {
+    if path.starts_with('/') {
+        return Some(path.into());
+    }
+    let mut chars = path.chars();
+    match (chars.next(), chars.next(), chars.next(), prefix) {
+        (Some(drive), Some(':'), Some('\\'), Some(prefix))
+        | (Some(drive), Some(':'), Some('/'), Some(prefix)) if drive.is_ascii_alphabetic() => {
+            let drive = format!("drive_{}", drive.to_ascii_lowercase());
+            let rest = path[3..].replace('\\', "/");
+            let rest = rest.trim_start_matches('/');
+            Some(prefix.join(drive).join(rest))
+        },
+        _ => None,
+    }
+}
+
+/// Joins each of `paths` onto the home directory, keeping the ones that exist
+pub(crate) fn home_dirs(paths: &[&str]) -> Vec<PathBuf> {
+    match home_dir() {
+        Some(home) => dedup_dirs(paths.iter().map(|p| home.join(p))),
+        None => Vec::new(),
+    }
+}
+
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Reads a file to a string, returning `None` if it doesn't exist
+pub(crate) fn read_optional(path: &Path) -> Result<Option<String>, LocateError> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(Some(s)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(LocateError::Io(path.into(), e)),
+    }
+}
+
+/// Recursively collects the state caches below `dir`, in sorted order. Symlinks aren't followed,
+/// since Wine prefixes link back to the root of the host filesystem. Directories that can't be
+/// read are skipped.
+pub fn find_cache_files(dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(v) => v,
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    debug!("Skipping {}: {}", dir.display(), e);
+                }
+                continue;
+            },
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let file_type = match entry.file_type() {
+                Ok(v) => v,
+                Err(..) => continue,
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() && path.extension().is_some_and(|ext| ext == CACHE_EXTENSION) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+}
@@ -0,0 +1,145 @@
+//! Steam libraries, and the caches DXVK writes below `steamapps` when run through Proton
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use super::{
+    dedup_dirs,
+    vdf,
+    FoundCache,
+    Launcher,
+    LocateError,
+};
+
+/// Locations a Steam installation is commonly found at, relative to the home directory. The
+/// first one is usually a symlink to the second.
+const ROOTS: [&str; 3] = [
+    ".steam/steam",
+    ".local/share/Steam",
+    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+];
+
+/// Steam installations present in the current user's home directory
+pub fn default_roots() -> Vec<PathBuf> {
+    super::home_dirs(&ROOTS)
+}
+
+fn read_vdf(path: &Path) -> Result<Option<vdf::Value>, LocateError> {
+    super::read_optional(path)?
+        .map(|s| vdf::parse(&s).map_err(|e| LocateError::Vdf(path.into(), e)))
+        .transpose()
+}
+
+/// All library folders of the Steam installation at `root`, including the root itself. Only the
+/// root is left if `libraryfolders.vdf` can't be parsed.
+pub fn library_folders(root: &Path) -> Result<Vec<PathBuf>, LocateError> {
+    let mut libraries = vec![root.to_path_buf()];
+    let path = root.join("steamapps").join("libraryfolders.vdf");
+    if let Some(doc) = super::skip_malformed(read_vdf(&path))? {
+        let folders = doc.get("libraryfolders").map(vdf::Value::fields).unwrap_or(&[]);
+        for (key, value) in folders {
+            if key.parse::<u32>().is_err() {
+                continue;
+            }
+            // Older versions of the file map indices directly to paths
+            let path = match value {
+                vdf::Value::String(path) => Some(path.as_str()),
+                vdf::Value::Object(..) => value.get_str("path"),
+            };
+            libraries.extend(path.map(PathBuf::from));
+        }
+    }
+    Ok(dedup_dirs(libraries))
+}
+
+/// The parts of an `appmanifest_<appid>.acf` we care about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppManifest {
+    pub appid:      String,
+    pub name:       Option<String>,
+    pub installdir: Option<String>,
+}
+
+/// Manifests of the apps installed in a library folder
+pub fn app_manifests(library: &Path) -> Result<Vec<AppManifest>, LocateError> {
+    let steamapps = library.join("steamapps");
+    let entries = match fs::read_dir(&steamapps) {
+        Ok(v) => v,
+        Err(e) => return Err(LocateError::Io(steamapps, e)),
+    };
+    let mut manifests = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !(name.starts_with("appmanifest_") && name.ends_with(".acf")) {
+            continue;
+        }
+        let doc = match super::skip_malformed(read_vdf(&entry.path()))? {
+            Some(v) => v,
+            None => continue,
+        };
+        let state = match doc.get("AppState") {
+            Some(v) => v,
+            None => continue,
+        };
+        if let Some(appid) = state.get_str("appid") {
+            manifests.push(AppManifest {
+                appid: appid.into(),
+                name: state.get_str("name").map(From::from),
+                installdir: state.get_str("installdir").map(From::from),
+            });
+        }
+    }
+    manifests.sort_by(|a, b| a.appid.cmp(&b.appid));
+    Ok(manifests)
+}
+
+/// Subdirectories of `dir`, keyed by name
+fn subdirs(dir: &Path) -> BTreeMap<String, PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| (e.file_name().to_string_lossy().into_owned(), e.path()))
+        .collect()
+}
+
+/// Finds the state caches of every game in all libraries of the Steam installation at `root`.
+///
+/// Proton points DXVK at `steamapps/shadercache/<appid>/DXVK_state_cache`, but caches also end
+/// up inside the game's Wine prefix below `steamapps/compatdata/<appid>`.
+pub fn find_caches(root: &Path) -> Result<Vec<FoundCache>, LocateError> {
+    let mut found = Vec::new();
+    for library in library_folders(root)? {
+        let steamapps = library.join("steamapps");
+        if !steamapps.is_dir() {
+            continue;
+        }
+        let names: BTreeMap<String, String> = app_manifests(&library)?
+            .into_iter()
+            .filter_map(|m| {
+                let appid = m.appid;
+                m.name.map(|name| (appid, name))
+            })
+            .collect();
+        let shadercache = subdirs(&steamapps.join("shadercache"))
+            .into_iter()
+            .map(|(appid, dir)| (appid, dir.join("DXVK_state_cache")));
+        let compatdata = subdirs(&steamapps.join("compatdata")).into_iter();
+        for (appid, dir) in shadercache.chain(compatdata) {
+            for path in super::find_cache_files(&dir) {
+                found.push(FoundCache {
+                    launcher: Launcher::Steam,
+                    game_name: names.get(&appid).cloned(),
+                    game_id: appid.clone(),
+                    path: path,
+                });
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
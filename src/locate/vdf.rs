@@ -0,0 +1,156 @@
+//! Parser for Valve's text KeyValues format, as used by `libraryfolders.vdf` and
+//! `appmanifest_*.acf`
+
+use std::{
+    iter::Peekable,
+    str::CharIndices,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Looks up `key` in an object. Keys are matched case-insensitively, like Steam does.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            Value::String(..) => None,
+        }
+    }
+
+    #[inline]
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_str)
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            Value::Object(..) => None,
+        }
+    }
+
+    /// The fields of an object, or nothing for a string
+    pub fn fields(&self) -> &[(String, Value)] {
+        match self {
+            Value::Object(fields) => fields,
+            Value::String(..) => &[],
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VdfError {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("unexpected '{1}' at byte {0}")]
+    Unexpected(usize, char),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Str(String),
+    Open,
+    Close,
+}
+
+struct Tokens<'a> {
+    src:   &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Tokens<'a> {
+    fn skip_space_and_comments(&mut self) {
+        while let Some(&(i, c)) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.chars.next();
+            } else if self.src[i..].starts_with("//") {
+                for (_, c) in self.chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<(usize, Token)>, VdfError> {
+        self.skip_space_and_comments();
+        let (start, c) = match self.chars.next() {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let token = match c {
+            '{' => Token::Open,
+            '}' => Token::Close,
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match self.chars.next() {
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((_, c)) => s.push(c),
+                            None => return Err(VdfError::UnexpectedEof),
+                        },
+                        Some((_, c)) => s.push(c),
+                        None => return Err(VdfError::UnexpectedEof),
+                    }
+                }
+                Token::Str(s)
+            },
+            c => {
+                let mut s = String::new();
+                s.push(c);
+                while let Some(&(_, c)) = self.chars.peek() {
+                    if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                        break;
+                    }
+                    s.push(c);
+                    self.chars.next();
+                }
+                Token::Str(s)
+            },
+        };
+        Ok(Some((start, token)))
+    }
+}
+
+/// Parses a document into an object holding its top-level keys
+pub fn parse(src: &str) -> Result<Value, VdfError> {
+    let mut tokens = Tokens {
+        src: src,
+        chars: src.char_indices().peekable(),
+    };
+    parse_object(&mut tokens, false)
+}
+
+fn parse_object(tokens: &mut Tokens<'_>, nested: bool) -> Result<Value, VdfError> {
+    let mut fields = Vec::new();
+    loop {
+        let key = match tokens.next_token()? {
+            Some((_, Token::Str(key))) => key,
+            Some((_, Token::Close)) if nested => return Ok(Value::Object(fields)),
+            Some((i, Token::Close)) => return Err(VdfError::Unexpected(i, '}')),
+            Some((i, Token::Open)) => return Err(VdfError::Unexpected(i, '{')),
+            None if nested => return Err(VdfError::UnexpectedEof),
+            None => return Ok(Value::Object(fields)),
+        };
+        let value = match tokens.next_token()? {
+            Some((_, Token::Str(value))) => Value::String(value),
+            Some((_, Token::Open)) => parse_object(tokens, true)?,
+            Some((i, Token::Close)) => return Err(VdfError::Unexpected(i, '}')),
+            None => return Err(VdfError::UnexpectedEof),
+        };
+        fields.push((key, value));
+    }
+}
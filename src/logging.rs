@@ -5,9 +5,9 @@ macro_rules! crate_name {
 }
 
 #[cfg(debug_assertions)]
-const DEFAULT_FILTER: &'static str = concat!(crate_name!(), "=debug");
+const DEFAULT_FILTER: &str = concat!(crate_name!(), "=debug");
 #[cfg(not(debug_assertions))]
-const DEFAULT_FILTER: &'static str = concat!(crate_name!(), "=info");
+const DEFAULT_FILTER: &str = concat!(crate_name!(), "=info");
 
 pub fn init() {
     use env_logger::Env;
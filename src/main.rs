@@ -1,19 +1,19 @@
-mod dxvk;
-mod error;
 mod sep;
-pub mod read;
 mod logging;
 
 use std::{
+    collections::HashSet,
+    convert::TryFrom,
     env,
     ffi::OsStr,
     fs::{
         self,
         File,
     },
-    io::{self, BufReader, BufWriter},
+    io::{self, BufWriter, Write},
     path::{Path, PathBuf},
     num::NonZeroU32,
+    ops::RangeInclusive,
     cell::Cell,
     error::{
         Error as StdError,
@@ -26,20 +26,119 @@ use clap::{
     crate_description,
 };
 
-use dxvk::*;
-use error::Error;
+use dxvk_cache_tool::{
+    append::AppendTarget,
+    bundle,
+    dxvk::*,
+    error::{error_file, Error, ErrorKind, SkippedInput},
+    generate::{
+        GenerateConfig,
+        DEFAULT_STAGE_MASKS,
+    },
+    locate,
+    provenance::{Provenance, Record},
+    report::{ReportBuilder, ReportFormat},
+    resolve,
+    similarity,
+    client::Client,
+    compress::{self, Compression, Encoder},
+    server::{self, Server},
+    show,
+    store::Store,
+};
+#[cfg(feature = "browse")]
+use dxvk_cache_tool::browse;
 use linked_hash_map::LinkedHashMap;
 use sep::Separated;
-use read::FromReader;
 use log::*;
 
 #[derive(Debug, clap::Parser)]
-#[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+#[clap(
+    version = crate_version!(),
+    author = crate_authors!(),
+    about = crate_description!(),
+    after_help = EXIT_STATUS_HELP
+)]
 struct AppConfig {
+    #[clap(flatten)]
+    read_args: ReadArgs,
+    #[clap(
+        long,
+        global = true,
+        value_name = "zstd|xz|gzip",
+        help = "Compress output files. Compressed inputs are always detected and read."
+    )]
+    compress: Option<Compression>,
+    #[clap(
+        long,
+        global = true,
+        value_name = "text|json",
+        default_value = "text",
+        parse(try_from_str = parse_error_format),
+        help = "Report errors as log messages, or as a line of JSON on stderr with their kind, file and message, and the inputs --keep-going skipped"
+    )]
+    error_format: ErrorFormat,
     #[clap(subcommand)]
     command: Command,
 }
 
+#[derive(Debug, clap::Args)]
+struct ReadArgs {
+    #[clap(
+        long,
+        global = true,
+        value_name = "little|big",
+        help = "Byte order to read input files with, instead of detecting it from their headers"
+    )]
+    assume_endian: Option<Endian>,
+    #[clap(
+        long,
+        global = true,
+        value_name = "BYTES",
+        parse(try_from_str = parse_size),
+        help = "Refuse to read entries larger than this (accepts K, M and G suffixes)"
+    )]
+    max_entry_size: Option<u64>,
+    #[clap(
+        long,
+        global = true,
+        value_name = "BYTES",
+        parse(try_from_str = parse_size),
+        help = "Refuse to read caches whose entries add up to more than this (accepts K, M and G suffixes)"
+    )]
+    max_cache_size: Option<u64>,
+}
+
+impl ReadArgs {
+    fn options(&self) -> ReadOptions {
+        let mut limits = ReadLimits::default();
+        if let Some(size) = self.max_entry_size {
+            limits.max_entry_size = usize::try_from(size).unwrap_or(usize::MAX);
+        }
+        if let Some(size) = self.max_cache_size {
+            limits.max_cache_size = size;
+        }
+        ReadOptions {
+            endian: self.assume_endian,
+            limits: limits,
+        }
+    }
+}
+
+/// Parses a byte count with an optional binary K, M or G suffix
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (digits, shift) = match s.char_indices().last() {
+        Some((i, 'K')) | Some((i, 'k')) => (&s[..i], 10),
+        Some((i, 'M')) | Some((i, 'm')) => (&s[..i], 20),
+        Some((i, 'G')) | Some((i, 'g')) => (&s[..i], 30),
+        _ => (s, 0),
+    };
+    digits.parse::<u64>()
+        .map_err(|e| format!("invalid size \"{}\": {}", s, e))?
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size \"{}\" is too large", s))
+}
+
 #[derive(Debug, clap::Subcommand)]
 enum Command {
     #[clap(about = "Merge multiple state-cache files together")]
@@ -58,6 +157,8 @@ enum Command {
     ListEntries {
         #[clap(required = true, help = "dxvk-cache files")]
         files: Vec<PathBuf>,
+        #[clap(long, parse(from_flag), help = KEEP_GOING_HELP)]
+        keep_going: bool,
     },
     #[clap(about = "List SHA1 hashes of all entries present in the first file but not the second")]
     Difference(DifferenceConfig),
@@ -66,7 +167,777 @@ enum Command {
         output_file: PathBuf,
         #[clap(required = true)]
         files: Vec<PathBuf>,
+        #[clap(long, parse(from_flag), help = APPEND_HELP)]
+        append: bool,
+        #[clap(long, parse(from_flag), help = KEEP_GOING_HELP)]
+        keep_going: bool,
     },
+    #[clap(about = "Generate a synthetic state cache with valid entries")]
+    Generate(GenerateArgs),
+    #[clap(about = "Find the state caches of installed games")]
+    Locate(LocateArgs),
+    #[clap(about = "Show where DXVK puts the state cache of a game, and whether it's enabled")]
+    Where(WhereArgs),
+    #[clap(about = "Manage a local store holding one merged state cache per game")]
+    Store(StoreArgs),
+    #[cfg(target_os = "linux")]
+    #[clap(about = "Watch state caches and merge them once they stop changing")]
+    Watch(WatchArgs),
+    #[clap(about = "Serve the local store over HTTP")]
+    Serve(ServeArgs),
+    #[clap(about = "Upload state caches to a server to be merged into its store")]
+    Push(PushArgs),
+    #[clap(about = "Download a game's state cache from a server")]
+    Pull(PullArgs),
+    #[clap(about = "Pack several games' state caches into one file along with their metadata")]
+    Bundle(BundleArgs),
+    #[clap(about = "Show which inputs an entry of a merged cache came from")]
+    Blame(BlameArgs),
+    #[clap(about = "Compare the entries of state caches pairwise")]
+    Similarity(SimilarityArgs),
+    #[clap(about = "Show the header, position and raw data of a single entry")]
+    Show(ShowArgs),
+    #[cfg(feature = "browse")]
+    #[clap(about = "Browse the entries of a state cache interactively")]
+    Browse(BrowseArgs),
+}
+
+#[derive(Debug, clap::Args)]
+struct LocateArgs {
+    #[clap(
+        long = "steam-root",
+        value_name = "DIR",
+        help = "Steam installation to search, may be given multiple times [default: the usual locations in $HOME]"
+    )]
+    steam_roots:   Vec<PathBuf>,
+    #[clap(
+        long = "lutris-root",
+        value_name = "DIR",
+        help = "Directory of Lutris game configurations to search, may be given multiple times"
+    )]
+    lutris_roots:  Vec<PathBuf>,
+    #[clap(
+        long = "heroic-root",
+        value_name = "DIR",
+        help = "Heroic configuration directory to search, may be given multiple times"
+    )]
+    heroic_roots:  Vec<PathBuf>,
+    #[clap(
+        long = "bottles-root",
+        value_name = "DIR",
+        help = "Directory of bottles to search, may be given multiple times"
+    )]
+    bottles_roots: Vec<PathBuf>,
+    #[clap(
+        long = "launcher",
+        value_name = "NAME",
+        help = "Only search this launcher (steam, lutris, heroic or bottles), may be given multiple times"
+    )]
+    launchers:     Vec<locate::Launcher>,
+}
+
+impl LocateArgs {
+    fn roots(&self, launcher: locate::Launcher) -> Vec<PathBuf> {
+        let roots = match launcher {
+            locate::Launcher::Steam => &self.steam_roots,
+            locate::Launcher::Lutris => &self.lutris_roots,
+            locate::Launcher::Heroic => &self.heroic_roots,
+            locate::Launcher::Bottles => &self.bottles_roots,
+        };
+        if roots.is_empty() {
+            launcher.default_roots()
+        } else {
+            roots.clone()
+        }
+    }
+
+    /// The caches of every selected launcher, searching the given or default roots
+    fn find(&self) -> Result<Vec<locate::FoundCache>, locate::LocateError> {
+        let launchers = if self.launchers.is_empty() {
+            &locate::Launcher::ALL[..]
+        } else {
+            &self.launchers[..]
+        };
+        let mut found = Vec::new();
+        for &launcher in launchers.iter() {
+            let roots = self.roots(launcher);
+            if roots.is_empty() {
+                info!("No {} installation found", launcher);
+            }
+            for root in roots.iter() {
+                debug!("Searching {} at {}", launcher, root.display());
+                found.extend(launcher.find_caches(root)?);
+            }
+        }
+        Ok(found)
+    }
+
+    fn run(self) -> Result<(), Box<dyn StdError + 'static>> {
+        let found = self.find()?;
+        for cache in found.iter() {
+            println!(
+                "{}\t{}\t{}\t{}",
+                cache.launcher,
+                cache.game_id,
+                cache.game_name.as_deref().unwrap_or("-"),
+                cache.path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct WhereArgs {
+    #[clap(help = "The game's executable")]
+    exe:      PathBuf,
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "Read the game's environment from KEY=VALUE lines instead of the current environment"
+    )]
+    env_file: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "DXVK configuration file [default: $DXVK_CONFIG_FILE, or dxvk.conf next to the executable]"
+    )]
+    config:   Option<PathBuf>,
+}
+
+impl WhereArgs {
+    fn run(self) -> Result<(), Box<dyn StdError + 'static>> {
+        let env = match self.env_file.as_ref() {
+            Some(path) => resolve::read_env_file(path)?,
+            None => resolve::current_environment(),
+        };
+        let resolution = resolve::resolve(&self.exe, &env, self.config.as_deref())?;
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        println!("path: {}", resolution.path.display());
+        println!("exists: {}", yes_no(resolution.path.is_file()));
+        match resolution.config.as_ref() {
+            Some(path) => println!("config: {}", path.display()),
+            None => println!("config: none"),
+        }
+        match resolution.disabled.as_ref() {
+            Some(reason) => println!("enabled: no ({})", reason),
+            None => println!("enabled: yes"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct StoreArgs {
+    #[clap(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Store directory [default: $XDG_DATA_HOME/dxvk-cache-tool/store]"
+    )]
+    store:   Option<PathBuf>,
+    #[clap(subcommand)]
+    command: StoreCommand,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum StoreCommand {
+    #[clap(about = "Merge state caches into the store")]
+    Add {
+        #[clap(required = true, help = "dxvk-cache files")]
+        files: Vec<PathBuf>,
+        #[clap(long, help = "Game to store the caches under [default: each file's name without extension]")]
+        game:  Option<String>,
+    },
+    #[clap(about = "Write a game's stored cache to a file")]
+    Get {
+        game:   String,
+        #[clap(short, long, help = "Output file name [default: <game>.dxvk-cache]")]
+        output: Option<PathBuf>,
+    },
+    #[clap(about = "List the stored games with their cache versions and entry counts")]
+    List,
+    #[clap(about = "Remove games from the store, leaving their entries for gc")]
+    Rm {
+        #[clap(required = true)]
+        games: Vec<String>,
+    },
+    #[clap(about = "Delete stored entries no game references anymore")]
+    Gc,
+}
+
+impl StoreArgs {
+    fn run(self, options: &ReadOptions, compress: Option<Compression>) -> Result<(), Box<dyn StdError + 'static>> {
+        let root = match self.store {
+            Some(v) => v,
+            None => Store::default_root()?,
+        };
+        let store = Store::open(root, *options)?;
+        match self.command {
+            StoreCommand::Add { files, game } => {
+                for f in files.iter() {
+                    let game = match game.as_ref() {
+                        Some(v) => v.clone(),
+                        None => f.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                    };
+                    let summary = store.add(&game, f)?;
+                    info!(
+                        "Added {} new entries from {} to {}, now {} entries",
+                        summary.added,
+                        f.display(),
+                        game,
+                        summary.total
+                    );
+                }
+            },
+            StoreCommand::Get { game, output } => {
+                let cache = store.get(&game)?;
+                let output = output.unwrap_or_else(|| format!("{}.dxvk-cache", game).into());
+                info!("Writing {} entries to file {}", cache.entries.len(), output.display());
+                write_cache(&output, &cache, compress)?;
+            },
+            StoreCommand::List => {
+                for game in store.list()? {
+                    println!("{}\tv{}\t{}", game.game, game.version, game.entries);
+                }
+            },
+            StoreCommand::Rm { games } => {
+                for game in games.iter() {
+                    store.remove(game)?;
+                    info!("Removed {}", game);
+                }
+            },
+            StoreCommand::Gc => {
+                let summary = store.gc()?;
+                println!(
+                    "removed {} entries ({} bytes), kept {}",
+                    summary.removed,
+                    summary.freed,
+                    summary.kept
+                );
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, clap::Args)]
+struct WatchArgs {
+    #[clap(help = "Directories or caches to watch [default: the directories of caches `locate` finds]")]
+    paths:  Vec<PathBuf>,
+    #[clap(flatten)]
+    locate: LocateArgs,
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value = "30",
+        help = "Merge a cache once it hasn't changed for this long"
+    )]
+    idle:   u64,
+    #[clap(short, long, value_name = "FILE", help = "Merge into this master cache instead of the store")]
+    output: Option<PathBuf>,
+    #[clap(long, value_name = "DIR", help = "Store directory [default: $XDG_DATA_HOME/dxvk-cache-tool/store]")]
+    store:  Option<PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl WatchArgs {
+    fn run(self, options: &ReadOptions) -> Result<(), Box<dyn StdError + 'static>> {
+        use dxvk_cache_tool::watch::{Sink, Watcher};
+        let mut dirs: Vec<PathBuf> = if self.paths.is_empty() {
+            self.locate.find()?
+                .into_iter()
+                .filter_map(|cache| cache.path.parent().map(Path::to_path_buf))
+                .collect()
+        } else {
+            self.paths.iter()
+                .map(|p| if p.is_file() { p.parent().unwrap_or(p).to_path_buf() } else { p.clone() })
+                .collect()
+        };
+        dirs.sort();
+        dirs.dedup();
+        if dirs.is_empty() {
+            return Err(Box::from("No state cache locations to watch"));
+        }
+        let sink = match (self.output, self.store) {
+            (Some(output), _) => Sink::File(output),
+            (None, Some(root)) => Sink::Store(Store::open(root, *options)?),
+            (None, None) => Sink::Store(Store::open(Store::default_root()?, *options)?),
+        };
+        let mut watcher = Watcher::new(dirs.iter().map(PathBuf::as_path), std::time::Duration::from_secs(self.idle))?;
+        watcher.run(&sink, options)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct ServeArgs {
+    #[clap(long, value_name = "ADDR", default_value = "127.0.0.1:8420", help = "Address to listen on")]
+    listen: String,
+    #[clap(long, value_name = "DIR", help = "Store directory [default: $XDG_DATA_HOME/dxvk-cache-tool/store]")]
+    store:  Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "BYTES",
+        parse(try_from_str = parse_size),
+        help = "Refuse uploads larger than this, compressed or not (accepts K, M and G suffixes) [default: 64M]"
+    )]
+    max_upload_size: Option<u64>,
+}
+
+impl ServeArgs {
+    fn run(self, options: &ReadOptions) -> Result<(), Box<dyn StdError + 'static>> {
+        let root = match self.store {
+            Some(v) => v,
+            None => Store::default_root()?,
+        };
+        let store = Store::open(root, *options)?;
+        let max_upload_size = self.max_upload_size.unwrap_or(server::MAX_UPLOAD_SIZE);
+        let server = Server::bind_with_upload_limit(&self.listen, store, *options, max_upload_size)?;
+        match server.local_addr() {
+            Some(addr) => info!("Serving {} on http://{}", server.store().root().display(), addr),
+            None => info!("Serving {}", server.store().root().display()),
+        }
+        server.run()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct PushArgs {
+    #[clap(help = "Server URL, e.g. http://hub:8420")]
+    url:   String,
+    #[clap(required = true, help = "dxvk-cache files")]
+    files: Vec<PathBuf>,
+    #[clap(long, help = "Game to store the caches under [default: each file's name without extension]")]
+    game:  Option<String>,
+}
+
+impl PushArgs {
+    fn run(self, options: &ReadOptions) -> Result<(), Box<dyn StdError + 'static>> {
+        let client = Client::new(&self.url, *options);
+        for f in self.files.iter() {
+            let game = match self.game.as_ref() {
+                Some(v) => v.clone(),
+                None => f.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            };
+            let summary = client.push(&game, f)?;
+            info!(
+                "Pushed {} to {}: {} new entries, now {} entries",
+                f.display(),
+                game,
+                summary.added,
+                summary.total
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct PullArgs {
+    #[clap(help = "Server URL, e.g. http://hub:8420")]
+    url:    String,
+    #[clap(required_unless_present = "list", help = "Game to download")]
+    game:   Option<String>,
+    #[clap(short, long, help = "Output file name [default: <game>.dxvk-cache]")]
+    output: Option<PathBuf>,
+    #[clap(long, conflicts_with = "game", help = "List the server's games instead")]
+    list:   bool,
+}
+
+impl PullArgs {
+    fn run(self, options: &ReadOptions, compress: Option<Compression>) -> Result<(), Box<dyn StdError + 'static>> {
+        let client = Client::new(&self.url, *options);
+        let game = match self.game {
+            Some(v) => v,
+            None => {
+                for game in client.list()? {
+                    println!("{}\tv{}\t{}", game.game, game.version, game.entries);
+                }
+                return Ok(());
+            },
+        };
+        let cache = client.pull(&game)?;
+        let output = self.output.unwrap_or_else(|| format!("{}.dxvk-cache", game).into());
+        info!("Writing {} entries to file {}", cache.entries.len(), output.display());
+        write_cache(&output, &cache, compress)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct BundleArgs {
+    #[clap(subcommand)]
+    command: BundleCommand,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum BundleCommand {
+    #[clap(about = "Create a bundle from state caches")]
+    Create {
+        #[clap(short, long, value_name = "FILE")]
+        output:       PathBuf,
+        #[clap(
+            required = true,
+            value_name = "[GAME=]FILE",
+            help = "dxvk-cache files, stored under GAME [default: each file's name without extension]"
+        )]
+        files:        Vec<String>,
+        #[clap(long, value_name = "VERSION", help = "DXVK version that wrote the caches")]
+        dxvk_version: Option<String>,
+        #[clap(long, value_name = "NAME", help = "Who contributed the caches [default: $USER]")]
+        contributor:  Option<String>,
+    },
+    #[clap(about = "List the games in bundles with their metadata")]
+    List {
+        #[clap(required = true)]
+        bundles: Vec<PathBuf>,
+    },
+    #[clap(about = "Write the caches in a bundle to separate files")]
+    Extract {
+        bundle: PathBuf,
+        #[clap(help = "Games to extract [default: all]")]
+        games:  Vec<String>,
+        #[clap(short = 'd', long, value_name = "DIR", default_value = ".", help = "Directory to write the caches to")]
+        dir:    PathBuf,
+    },
+    #[clap(about = "Merge bundles, combining the caches of games they have in common")]
+    Merge {
+        #[clap(short, long, value_name = "FILE")]
+        output:  PathBuf,
+        #[clap(required = true)]
+        bundles: Vec<PathBuf>,
+    },
+}
+
+/// Joins the distinct values of `a` and `b`, for metadata of merged caches
+fn join_metadata(a: Option<String>, b: Option<String>) -> Option<String> {
+    match (a, b) {
+        (Some(a), Some(b)) if a.split(", ").any(|v| v == b) => Some(a),
+        (Some(a), Some(b)) => Some(format!("{}, {}", a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+impl BundleArgs {
+    fn run(self, options: &ReadOptions, compress: Option<Compression>) -> Result<(), Box<dyn StdError + 'static>> {
+        match self.command {
+            BundleCommand::Create { output, files, dxvk_version, contributor } => {
+                let contributor = contributor.or_else(|| env::var("USER").ok());
+                let mut caches: LinkedHashMap<String, DxvkStateCache> = LinkedHashMap::new();
+                for arg in files.iter() {
+                    let (game, path) = match arg.split_once('=') {
+                        Some((game, path)) => (game.to_owned(), Path::new(path)),
+                        None => {
+                            let path = Path::new(arg);
+                            (path.file_stem().unwrap_or_default().to_string_lossy().into_owned(), path)
+                        },
+                    };
+                    let cache = DxvkStateCache::from_file(path, options)?;
+                    match caches.get_mut(&game) {
+                        Some(stored) => *stored = stored.merge(&cache).map_err(|e| e.in_file(path))?,
+                        None => {
+                            caches.insert(game, cache);
+                        },
+                    }
+                }
+                let caches: Vec<_> = caches.into_iter()
+                    .map(|(game, cache)| (bundle::Metadata::new(game, dxvk_version.clone(), contributor.clone()), cache))
+                    .collect();
+                info!("Writing {} games to bundle {}", caches.len(), output.display());
+                let mut f = open_output(&output, compress)?;
+                bundle::write_bundle(&mut f, &caches)?;
+                f.finish()?;
+            },
+            BundleCommand::List { bundles } => {
+                for path in bundles.iter() {
+                    let reader = bundle::open(path, options)?;
+                    for e in reader.index() {
+                        println!(
+                            "{}\tv{}\t{}\t{}\t{}\t{}",
+                            e.metadata.game,
+                            e.version,
+                            e.entries,
+                            e.metadata.dxvk_version.as_deref().unwrap_or("-"),
+                            e.metadata.contributor.as_deref().unwrap_or("-"),
+                            bundle::format_timestamp(e.metadata.created)
+                        );
+                    }
+                }
+            },
+            BundleCommand::Extract { bundle, games, dir } => {
+                let reader = bundle::open(&bundle, options)?;
+                if let Some(game) = games.iter().find(|&g| !reader.index().iter().any(|e| &e.metadata.game == g)) {
+                    return Err(format!("{} is not in {}", game, bundle.display()).into());
+                }
+                fs::create_dir_all(&dir)?;
+                for next in reader {
+                    let (entry, cache) = next?;
+                    if !games.is_empty() && !games.contains(&entry.metadata.game) {
+                        continue;
+                    }
+                    let output = dir.join(format!("{}.dxvk-cache", entry.metadata.game));
+                    info!("Writing {} entries to file {}", cache.entries.len(), output.display());
+                    write_cache(&output, &cache, compress)?;
+                }
+            },
+            BundleCommand::Merge { output, bundles } => {
+                let mut caches: LinkedHashMap<String, (bundle::Metadata, DxvkStateCache)> = LinkedHashMap::new();
+                for path in bundles.iter() {
+                    for next in bundle::open(path, options)? {
+                        let (entry, cache) = next?;
+                        let (metadata, stored) = match caches.get_mut(&entry.metadata.game) {
+                            Some(v) => v,
+                            None => {
+                                caches.insert(entry.metadata.game.clone(), (entry.metadata, cache));
+                                continue;
+                            },
+                        };
+                        *stored = stored.merge(&cache)
+                            .map_err(|e| format!("{}: {}: {}", path.display(), metadata.game, e))?;
+                        metadata.dxvk_version = join_metadata(metadata.dxvk_version.take(), entry.metadata.dxvk_version);
+                        metadata.contributor = join_metadata(metadata.contributor.take(), entry.metadata.contributor);
+                        metadata.created = metadata.created.max(entry.metadata.created);
+                    }
+                }
+                let caches: Vec<_> = caches.into_iter().map(|(_, v)| v).collect();
+                info!("Writing {} games to bundle {}", caches.len(), output.display());
+                let mut f = open_output(&output, compress)?;
+                bundle::write_bundle(&mut f, &caches)?;
+                f.finish()?;
+            },
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct BlameArgs {
+    #[clap(help = "State cache merged with --provenance")]
+    cache: PathBuf,
+    #[clap(help = "Hash of the entry, or a unique prefix of it")]
+    hash:  HashPrefix,
+}
+
+/// The only item of `matches`, the entries of `file` matching `hash`
+fn unique_match<T, I, F>(matches: I, hash: &HashPrefix, file: &Path, hash_of: F) -> Result<T, Box<dyn StdError + 'static>>
+where
+    I: Iterator<Item=T>,
+    F: Fn(&T) -> &Sha1Hash,
+{
+    let mut matches: Vec<_> = matches.collect();
+    match matches.len() {
+        0 => Err(Error::NoMatchingHash {
+            prefix: hash.to_string(),
+            file: file.to_path_buf(),
+        }.into()),
+        1 => Ok(matches.remove(0)),
+        _ => Err(Error::AmbiguousHash {
+            prefix: hash.to_string(),
+            matches: matches.iter().map(|m| HashDisplay::new(hash_of(m)).to_string()).collect(),
+        }.into()),
+    }
+}
+
+impl BlameArgs {
+    fn run(self, options: &ReadOptions) -> Result<(), Box<dyn StdError + 'static>> {
+        let cache = DxvkStateCache::from_file(&self.cache, options)?;
+        let matches = cache.iter()
+            .map(|e| e.hash)
+            .filter(|h| self.hash.matches(h));
+        let hash = unique_match(matches, &self.hash, &self.cache, |h| h)?;
+        let provenance = Provenance::load(&self.cache)?
+            .ok_or_else(|| format!("{} has no provenance, merge it with --provenance", self.cache.display()))?;
+        let records = provenance.get(&hash);
+        if records.is_empty() {
+            return Err(format!("No provenance recorded for {}", HashDisplay::new(&hash)).into());
+        }
+        for r in records {
+            println!("{}\t{}\t{}", r.source, r.contributor, r.time);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct ShowArgs {
+    #[clap(help = "dxvk-cache file")]
+    file: PathBuf,
+    #[clap(help = "Hash of the entry, or a unique prefix of it")]
+    hash: HashPrefix,
+}
+
+impl ShowArgs {
+    fn run(self, options: &ReadOptions) -> Result<(), Box<dyn StdError + 'static>> {
+        let (header, entries) = compress::open(&self.file)
+            .map_err(ReadError::from)
+            .and_then(|r| show::read_entries(r, options))
+            .map_err(|e| e.in_file(&self.file))?;
+        let entry = unique_match(show::find(&entries, &self.hash), &self.hash, &self.file, |e| &e.entry.hash)?;
+        show::write_details(io::stdout().lock(), &header, entry)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "browse")]
+#[derive(Debug, clap::Args)]
+struct BrowseArgs {
+    #[clap(help = "dxvk-cache file")]
+    file:   PathBuf,
+    #[clap(
+        long,
+        value_name = "FILE",
+        default_value = "export.dxvk-cache",
+        help = "Where to write the entries marked for export, asking before overwriting it"
+    )]
+    export: PathBuf,
+    #[clap(
+        long,
+        short,
+        value_name = "FILE",
+        default_value = "output.dxvk-cache",
+        help = "Where to write the entries not marked for removal, asking before overwriting it"
+    )]
+    output: PathBuf,
+}
+
+#[cfg(feature = "browse")]
+impl BrowseArgs {
+    fn run(self, options: &ReadOptions, compress: Option<Compression>) -> Result<(), Box<dyn StdError + 'static>> {
+        let browser = browse::open(&self.file, options)?;
+        let outputs = browse::Outputs {
+            export: self.export,
+            output: self.output,
+            compress: compress,
+        };
+        browse::browse(&self.file, browser, outputs)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct SimilarityArgs {
+    #[clap(required = true, help = "dxvk-cache files")]
+    files:       Vec<PathBuf>,
+    #[clap(
+        long,
+        parse(from_flag),
+        help = "Show how much of each row's entries the column has, instead of Jaccard similarity"
+    )]
+    containment: bool,
+    #[clap(
+        long,
+        value_name = "THRESHOLD",
+        parse(try_from_str = parse_fraction),
+        help = "Also group files whose Jaccard similarity is at least THRESHOLD, between 0 and 1"
+    )]
+    cluster:     Option<f64>,
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        _ => Err(format!("\"{}\" is not a number between 0 and 1", s)),
+    }
+}
+
+impl SimilarityArgs {
+    fn run(self, options: &ReadOptions) -> Result<(), Box<dyn StdError + 'static>> {
+        let mut sets = Vec::with_capacity(self.files.len());
+        for f in self.files.iter() {
+            let cache = DxvkStateCache::from_file(f, options)?;
+            sets.push(cache.iter().map(|e| e.hash).collect::<HashSet<_>>());
+        }
+        let matrix = if self.containment {
+            similarity::matrix(&sets, similarity::containment)
+        } else {
+            similarity::matrix(&sets, similarity::jaccard)
+        };
+        println!("\t{}", Separated::new("\t", || self.files.iter().map(|f| f.display())));
+        for (f, row) in self.files.iter().zip(matrix.iter()) {
+            println!("{}\t{}", f.display(), Separated::new("\t", || row.iter().map(|v| format!("{:.3}", v))));
+        }
+        if let Some(threshold) = self.cluster {
+            println!();
+            for (i, cluster) in similarity::clusters(&sets, threshold).iter().enumerate() {
+                let union: HashSet<_> = cluster.iter().flat_map(|&f| sets[f].iter()).collect();
+                println!(
+                    "{}\t{}\t{}",
+                    i + 1,
+                    union.len(),
+                    Separated::new("\t", || cluster.iter().map(|&f| self.files[f].display()))
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct GenerateArgs {
+    #[clap(short, long, default_value = "output.dxvk-cache", help = "Output file name")]
+    output: PathBuf,
+    #[clap(long, help = "State cache version to generate")]
+    cache_version: NonZeroU32,
+    #[clap(short = 'n', long, help = "Number of entries to generate")]
+    count: usize,
+    #[clap(
+        long,
+        default_value = "256-4096",
+        value_name = "SIZE|MIN-MAX",
+        parse(try_from_str = parse_size_range),
+        help = "Entry payload size, or range of sizes (accepts K, M and G suffixes)"
+    )]
+    size: RangeInclusive<usize>,
+    #[clap(
+        long = "stage-mask",
+        value_name = "MASK",
+        parse(try_from_str = parse_stage_mask),
+        help = "Stage mask to pick from for each entry, may be given multiple times [default: 0x11 0x20]"
+    )]
+    stage_masks: Vec<u8>,
+    #[clap(long, default_value = "0", help = "Seed for the generated entry contents")]
+    seed: u64,
+}
+
+impl GenerateArgs {
+    fn run(self, compress: Option<Compression>) -> Result<(), Box<dyn StdError + 'static>> {
+        let config = GenerateConfig {
+            version: self.cache_version,
+            count: self.count,
+            data_sizes: self.size,
+            stage_masks: if self.stage_masks.is_empty() {
+                DEFAULT_STAGE_MASKS.to_vec()
+            } else {
+                self.stage_masks
+            },
+            seed: self.seed,
+        };
+        config.validate()?;
+        info!("Generating {} v{} entries to file {}", config.count, config.version, self.output.display());
+        let mut f = open_output(&self.output, compress)?;
+        config.write_to(&mut f)?;
+        f.finish()?;
+        Ok(())
+    }
+}
+
+fn parse_size_range(s: &str) -> Result<RangeInclusive<usize>, String> {
+    let to_usize = |v: u64| usize::try_from(v).map_err(|e| e.to_string());
+    match s.split_once('-') {
+        Some((min, max)) => Ok(to_usize(parse_size(min)?)?..=to_usize(parse_size(max)?)?),
+        None => parse_size(s).and_then(to_usize).map(|v| v..=v),
+    }
+}
+
+fn parse_stage_mask(s: &str) -> Result<u8, String> {
+    let res = match s.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|e| format!("invalid stage mask \"{}\": {}", s, e))
 }
 
 #[derive(Debug, clap::Args)]
@@ -85,8 +956,59 @@ struct MergeConfig {
     files: Vec<PathBuf>,
     #[clap(long, parse(from_flag))]
     dry_run: bool,
+    #[clap(long, parse(from_flag), help = APPEND_HELP)]
+    append: bool,
+    #[clap(
+        long,
+        parse(from_flag),
+        help = "Record where each entry came from in <OUTPUT>.provenance, for `blame`"
+    )]
+    provenance: bool,
+    #[clap(long, value_name = "NAME", help = "Who contributed the inputs, for --provenance [default: $USER]")]
+    contributor: Option<String>,
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "Write each input's total, unique, shared and omitted entry counts, as JSON if FILE ends in .json"
+    )]
+    report: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "latest|N|first",
+        parse(try_from_str = parse_target_version),
+        help = "Merge the inputs of this cache version, skipping the others instead of failing. \
+                Entries are never converted between versions."
+    )]
+    target_version: Option<TargetVersion>,
+    #[clap(long, parse(from_flag), help = KEEP_GOING_HELP)]
+    keep_going: bool,
+}
+
+const KEEP_GOING_HELP: &str = "Skip inputs that can't be read instead of failing, exiting with status 3 if any were";
+
+/// Which cache version a merge of mixed versions produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TargetVersion {
+    /// The highest version among the inputs
+    Latest,
+    /// The version of the first input
+    First,
+    Version(NonZeroU32),
+}
+
+fn parse_target_version(s: &str) -> Result<TargetVersion, String> {
+    match s {
+        "latest" => Ok(TargetVersion::Latest),
+        "first" => Ok(TargetVersion::First),
+        _ => s.trim_start_matches('v')
+            .parse()
+            .map(TargetVersion::Version)
+            .map_err(|_| format!("\"{}\" is neither latest, first nor a cache version", s)),
+    }
 }
 
+const APPEND_HELP: &str = "Keep the output file's existing contents, only appending entries it lacks";
+
 #[derive(Clone, Copy, PartialEq)]
 struct HeaderInfo {
     version: NonZeroU32,
@@ -105,10 +1027,10 @@ impl<'a> From<&'a DxvkStateCacheHeader> for HeaderInfo {
     }
 }
 
-impl Into<DxvkStateCacheHeader> for HeaderInfo {
+impl From<HeaderInfo> for DxvkStateCacheHeader {
     #[inline(always)]
-    fn into(self) -> DxvkStateCacheHeader {
-        DxvkStateCacheHeader::new(self.version, self.entry_size)
+    fn from(info: HeaderInfo) -> Self {
+        DxvkStateCacheHeader::new(info.version, info.entry_size)
     }
 }
 
@@ -129,16 +1051,16 @@ impl From<MergeConfig> for LegacyMergeConfig {
 }
 
 impl LegacyMergeConfig {
-    pub fn check_header(&self, header: &DxvkStateCacheHeader) -> Result<(), Error> {
+    pub fn check_header(&self, path: &Path, header: &DxvkStateCacheHeader) -> Result<(), Error> {
         match self.header_info.get() {
             None => {
                 self.header_info.set(Some(HeaderInfo::from(header)));
                 info!("Detected state cache version v{}", header.version);
                 Ok(())
             },
-            Some(HeaderInfo { version, .. }) if version != header.version =>
-                Err(Error::version_mismatch(version, header.version)),
-            Some(..) => Ok(()),
+            Some(info) => DxvkStateCacheHeader::from(info)
+                .check_compatible(header)
+                .map_err(|e| e.in_file(path).into()),
         }
     }
 
@@ -149,20 +1071,84 @@ impl LegacyMergeConfig {
 }
 
 impl MergeConfig {
-    fn run(self) -> Result<(), Error> {
+    fn run(self, options: &ReadOptions, compress: Option<Compression>) -> Result<(), Error> {
         let dry_run = self.dry_run;
+        let append = self.append;
+        let keep_going = self.keep_going;
+        let contributor = self.contributor.clone()
+            .or_else(|| env::var("USER").ok())
+            .unwrap_or_default();
+        let mut provenance = match (self.provenance, append) {
+            (true, true) => Some(Provenance::load(&self.output)?.unwrap_or_default()),
+            (true, false) => Some(Provenance::default()),
+            (false, _) => None,
+        };
+        let report = self.report.clone();
+        let mut report_builder = ReportBuilder::default();
+        let skip_other_versions = self.target_version.is_some();
+        let target_version = self.target_version;
         let config: LegacyMergeConfig = self.into();
+        let mut inputs: Box<dyn Iterator<Item=_>> = Box::new(
+            config.files.iter().map(|path| read_valid_entries(path, options))
+        );
+        let target_version = match target_version {
+            Some(TargetVersion::Version(v)) => Some(v),
+            Some(TargetVersion::Latest) => {
+                // Reads every input up front, so one that can't be read doesn't pick the version
+                let read: Vec<_> = inputs.collect();
+                let latest = read.iter().flatten().map(|(header, ..)| header.version).max();
+                inputs = Box::new(read.into_iter());
+                latest
+            },
+            Some(TargetVersion::First) | None => None,
+        };
+        if let Some(version) = target_version {
+            info!("Merging into state cache version v{}", version);
+        }
 
         info!("Merging files: {}", Separated::new(" ", || config.files().map(|p| p.display())));
         let mut entries = LinkedHashMap::new();
-        for (i, path) in config.files.iter().enumerate() {
-            let file = File::open(path)?;
-            let mut reader = BufReader::new(file);
-
-            let header = DxvkStateCacheHeader::from_reader(&mut reader)?;
-            config.check_header(&header)?;
+        let mut skipped = 0;
+        let mut unmergeable = KeepGoing::new(keep_going, config.files.len());
+        for ((i, path), input) in config.files.iter().enumerate().zip(inputs) {
+            let (header, input, omitted) = match unmergeable.check(path, input)? {
+                Some(v) => v,
+                None => continue,
+            };
+            // Entries can't be converted between versions, since their contents aren't decoded
+            let target = target_version.or_else(|| config.header_info.get().map(|h| h.version));
+            match target {
+                Some(version) if skip_other_versions && version != header.version => {
+                    warn!(
+                        "Skipping {}: v{} entries cannot be converted to v{}",
+                        path.display(),
+                        header.version,
+                        version
+                    );
+                    report_builder.skip_input(path, header.version);
+                    skipped += 1;
+                    continue;
+                },
+                _ => {
+                    if unmergeable.check(path, config.check_header(path, &header))?.is_none() {
+                        report_builder.skip_input(path, header.version);
+                        continue;
+                    }
+                },
+            }
+            // Entries of an input that was merged itself keep their original sources
+            let input_provenance = match provenance {
+                Some(..) => match unmergeable.check(path, Provenance::load(path))? {
+                    Some(v) => v,
+                    None => {
+                        report_builder.skip_input(path, header.version);
+                        continue;
+                    },
+                },
+                None => None,
+            };
 
-            let mut omitted = 0;
+            let mut hashes = HashSet::new();
             let entries_len = entries.len();
             info!(
                 "Merging {} ({}/{})... ",
@@ -170,23 +1156,34 @@ impl MergeConfig {
                 i + 1,
                 config.files.len()
             );
-            loop {
-                let res = DxvkStateCacheEntry::from_reader(&mut reader, &header);
-                match res {
-                    Ok(e) => {
-                        entries.insert(e.hash, e);
-                    },
-                    Err(EntryError::HashMismatch) => {
-                        omitted += 1;
-                    },
-                    Err(EntryError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
-                    Err(EntryError::Io(e)) => return Err(e.into()),
+            for e in input {
+                if let Some(provenance) = provenance.as_mut() {
+                    let known = input_provenance.as_ref().map(|p| p.get(&e.hash)).unwrap_or(&[]);
+                    if known.is_empty() {
+                        provenance.add(e.hash, Record::new(path, &contributor));
+                    }
+                    for record in known {
+                        provenance.add(e.hash, record.clone());
+                    }
                 }
+                hashes.insert(e.hash);
+                entries.insert(e.hash, e);
             }
             info!("\t{} new entries", entries.len() - entries_len);
             if omitted > 0 {
                 warn!("\t{} entries are omitted as invalid", omitted);
             }
+            report_builder.add_input(path, hashes, omitted);
+        }
+        if skipped > 0 {
+            warn!("{} of {} inputs skipped for their cache version", skipped, config.files.len());
+        }
+        let outcome = unmergeable.outcome();
+
+        if let Some(path) = report {
+            let mut f = BufWriter::new(File::create(&path)?);
+            report_builder.build().write_to(&mut f, ReportFormat::for_path(&path))?;
+            f.flush()?;
         }
 
         if entries.is_empty() {
@@ -195,7 +1192,23 @@ impl MergeConfig {
 
         if dry_run {
             info!("{} entries when merged", entries.len());
-            return Ok(());
+            return outcome;
+        }
+
+        let header: DxvkStateCacheHeader = config.header_info.get().unwrap().into();
+
+        if append && compress.is_some() {
+            return Err(Error::AppendCompressed(config.output));
+        }
+        if append {
+            if let Some(target) = AppendTarget::open(&config.output, options)? {
+                target.check_header(&header)?;
+                append_entries(target, entries.into_iter().map(|(_, e)| e))?;
+                if let Some(provenance) = provenance {
+                    provenance.save(&config.output)?;
+                }
+                return outcome;
+            }
         }
 
         info!(
@@ -204,53 +1217,115 @@ impl MergeConfig {
             config.output.file_name().and_then(OsStr::to_str).unwrap()
         );
 
-        let header: DxvkStateCacheHeader = config.header_info.get().unwrap().into();
-
-        let file = File::create(&config.output)?;
-        let mut writer = BufWriter::new(file);
+        let mut writer = open_output(&config.output, compress)?;
         header.write_to(&mut writer)?;
         let write_edition = header.edition();
         for (_, entry) in &entries {
             entry.write_to(&mut writer, write_edition)?;
         }
+        writer.finish()?;
+        if let Some(provenance) = provenance {
+            info!("Recording provenance of {} entries", provenance.len());
+            provenance.save(&config.output)?;
+        }
 
         debug!("Finished");
 
-        Ok(())
+        outcome
+    }
+}
+
+/// Counts the inputs skipped with `--keep-going`. Without it, the first failure is returned
+/// instead.
+struct KeepGoing {
+    enabled: bool,
+    skipped: Vec<SkippedInput>,
+    total:   usize,
+}
+
+impl KeepGoing {
+    fn new(enabled: bool, total: usize) -> Self {
+        KeepGoing {
+            enabled: enabled,
+            skipped: Vec::new(),
+            total: total,
+        }
+    }
+
+    /// The value of the `result` of the input at `path`, or `None` if it failed and the input is
+    /// to be skipped
+    fn check<T, E: Into<Error>>(&mut self, path: &Path, result: Result<T, E>) -> Result<Option<T>, Error> {
+        match result {
+            Ok(v) => Ok(Some(v)),
+            Err(e) if self.enabled => {
+                let e = e.into();
+                warn!("Skipping {}", e);
+                self.skipped.push(SkippedInput {
+                    path: path.into(),
+                    kind: ErrorKind::of(&e),
+                    message: e.to_string(),
+                });
+                Ok(None)
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Partial success if any input was skipped
+    fn outcome(self) -> Result<(), Error> {
+        if self.skipped.is_empty() {
+            return Ok(());
+        }
+        Err(Error::partial_success(self.skipped, self.total))
     }
 }
 
-fn inspect<P: AsRef<Path>, Pfx: std::fmt::Display>(prefix: Option<&Pfx>, f: P) -> Result<(), ReadError> {
+fn inspect<P, Pfx>(prefix: Option<&Pfx>, f: P, options: &ReadOptions) -> Result<(), ReadError>
+where
+    P: AsRef<Path>,
+    Pfx: std::fmt::Display,
+{
     let prefix = if let Some(prefix) = prefix {
         println!("{}:", prefix);
         "\t"
     } else {
         ""
     };
-    let f = fs::OpenOptions::new()
-        .read(true)
-        .open(f)
-        .map(BufReader::new)?;
-    let cache = DxvkStateCache::from_reader(f)?;
+    let cache = DxvkStateCache::from_file(f, options)?;
     println!("{}version: {}", prefix, cache.header.version);
     println!("{}entries: {}", prefix, cache.entries.len());
     Ok(())
 }
 
+fn append_entries<It>(target: AppendTarget, entries: It) -> Result<(), Error>
+where
+    It: IntoIterator<Item=DxvkStateCacheEntry>,
+{
+    let edition = target.edition();
+    let entries: Vec<_> = entries.into_iter()
+        .filter(|e| !target.contains(e))
+        .collect();
+    info!(
+        "Appending {} new entries to file {}",
+        entries.len(),
+        target.path().display()
+    );
+    let mut writer = target.into_writer();
+    for entry in &entries {
+        entry.write_to(&mut writer, edition)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
 impl DifferenceConfig {
-    fn run(self) -> Result<(), Box<dyn StdError + 'static>> {
-        let mut fst = DxvkStateCache::from_file(self.first)?;
-        let snd = DxvkStateCache::from_file(self.second)?;
-        if fst.header.version != snd.header.version {
-            return Err(Box::new(io::Error::new(io::ErrorKind::Other, format!("version mismatch: v{} != v{}", fst.header.version, snd.header.version))));
-        }
-        fst.entries = fst.entries.difference(&snd.entries)
-            .map(Clone::clone)
-            .collect();
+    fn run(self, options: &ReadOptions, compress: Option<Compression>) -> Result<(), Box<dyn StdError + 'static>> {
+        let fst = DxvkStateCache::from_file(&self.first, options)?;
+        let snd = DxvkStateCache::from_file(&self.second, options)?;
+        let fst = fst.difference(&snd).map_err(|e| e.in_file(&self.second))?;
 
         if let Some(output_file) = self.output_file {
-            let f = open_output(output_file)?;
-            fst.write_to(f)?;
+            write_cache(output_file, &fst, compress)?;
         } else {
             fst.iter().for_each(|entry| {
                 println!("{}", entry.hash_display());
@@ -260,74 +1335,197 @@ impl DifferenceConfig {
     }
 }
 
+const EXIT_STATUS_HELP: &str = "EXIT STATUS:
+    0    Success
+    1    Any other error
+    2    Invalid arguments
+    3    Some inputs were skipped with --keep-going, the rest succeeded
+    4    No valid state cache entries found
+    5    State cache version mismatch
+    6    Corrupt or invalid input
+    7    Reading or writing a file failed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+fn parse_error_format(s: &str) -> Result<ErrorFormat, String> {
+    match s {
+        "text" => Ok(ErrorFormat::Text),
+        "json" => Ok(ErrorFormat::Json),
+        _ => Err(format!("unknown error format \"{}\"", s)),
+    }
+}
+
+/// Prints an error as a single line of JSON on stderr, along with the inputs skipped if any were
+fn print_json_error(kind: ErrorKind, file: Option<&Path>, message: &str, skipped: &[SkippedInput]) {
+    let mut error = serde_json::json!({
+        "kind": kind.name(),
+        "code": kind.exit_code(),
+        "file": file.map(|p| p.display().to_string()),
+        "message": message,
+    });
+    if !skipped.is_empty() {
+        error["skipped"] = skipped.iter()
+            .map(|s| serde_json::json!({
+                "file": s.path.display().to_string(),
+                "kind": s.kind.name(),
+                "code": s.kind.exit_code(),
+                "message": s.message,
+            }))
+            .collect();
+    }
+    eprintln!("{}", error);
+}
+
 #[inline(always)]
-fn run_main<F, E>(f: F)
+fn run_main<F>(format: ErrorFormat, f: F)
 where
-    F: FnOnce() -> Result<(), E>,
-    E: std::fmt::Display,
+    F: FnOnce() -> Result<(), Box<dyn StdError + 'static>>,
 {
     if let Err(e) = f() {
-        error!("{}", e);
-        process::exit(1);
+        let kind = ErrorKind::of(e.as_ref());
+        match format {
+            ErrorFormat::Json => {
+                let skipped = match e.downcast_ref() {
+                    Some(Error::PartialSuccess { skipped, .. }) => &skipped[..],
+                    _ => &[],
+                };
+                print_json_error(kind, error_file(e.as_ref()), &e.to_string(), skipped);
+            },
+            ErrorFormat::Text if kind == ErrorKind::PartialSuccess => warn!("{}", e),
+            ErrorFormat::Text => error!("{}", e),
+        }
+        process::exit(kind.exit_code());
     }
 }
 
-fn open_output<P: AsRef<Path>>(p: P) -> io::Result<fs::File> {
-    fs::OpenOptions::new()
+/// Whether JSON errors were asked for, for when the arguments can't be parsed
+fn json_errors_requested() -> bool {
+    let args: Vec<_> = env::args_os().collect();
+    args.windows(2).any(|w| w[0] == "--error-format" && w[1] == "json")
+        || args.iter().any(|arg| arg == "--error-format=json")
+}
+
+/// Writes `cache` to a new output file. Fails before creating the file if there's nothing to write.
+fn write_cache<P: AsRef<Path>>(p: P, cache: &DxvkStateCache, compress: Option<Compression>) -> Result<(), Error> {
+    if cache.entries.is_empty() {
+        return Err(Error::NoEntriesFound);
+    }
+    let mut f = open_output(p, compress)?;
+    cache.write_to(&mut f)?;
+    f.finish()?;
+    Ok(())
+}
+
+/// Creates an output file, which has to be `finish`ed once written
+fn open_output<P: AsRef<Path>>(p: P, compress: Option<Compression>) -> io::Result<Encoder<BufWriter<File>>> {
+    let f = fs::OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(p)
+        .open(p)?;
+    Encoder::new(BufWriter::new(f), compress)
 }
 
 fn main() {
+    use clap::Parser;
     logging::init();
-    run_main(|| -> Result<(), Box<dyn StdError + 'static>> {
-        use clap::Parser;
-        let config = AppConfig::parse();
+    let config = match AppConfig::try_parse() {
+        Ok(v) => v,
+        Err(e) if e.use_stderr() && json_errors_requested() => {
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or("").trim_start_matches("error: ");
+            print_json_error(ErrorKind::Usage, None, message, &[]);
+            process::exit(ErrorKind::Usage.exit_code());
+        },
+        Err(e) => e.exit(),
+    };
+    run_main(config.error_format, || -> Result<(), Box<dyn StdError + 'static>> {
+        let options = config.read_args.options();
+        let compress = config.compress;
         match config.command {
-            Command::Merge(cfg) => cfg.run().map_err(From::from),
+            Command::Merge(cfg) => cfg.run(&options, compress).map_err(From::from),
             Command::Inspect { files } => {
                 if files.len() == 1 {
-                    inspect::<_, String>(None, files.iter().next().unwrap())?;
+                    inspect::<_, String>(None, &files[0], &options)?;
                 } else {
                     for f in files.iter() {
-                        inspect(Some(&f.display()), f)?;
+                        inspect(Some(&f.display()), f, &options)?;
                     }
                 }
                 Ok(())
             },
             Command::Jumble { input_file, output_file } => {
-                let cache = DxvkStateCache::from_file(input_file)?;
-                let f = open_output(output_file)?;
-                cache.write_to(f)?;
+                let cache = DxvkStateCache::from_file(input_file, &options)?;
+                write_cache(output_file, &cache, compress)?;
                 Ok(())
             },
-            Command::ListEntries { files } => {
+            Command::ListEntries { files, keep_going } => {
+                let mut unreadable = KeepGoing::new(keep_going, files.len());
                 for f in files.iter() {
-                    let cache = DxvkStateCache::from_file(f)?;
+                    let cache = match unreadable.check(f, DxvkStateCache::from_file(f, &options))? {
+                        Some(v) => v,
+                        None => continue,
+                    };
                     cache.iter().for_each(|entry| {
                         println!("{}", entry.hash_display());
                     });
                 }
-                Ok(())
+                unreadable.outcome().map_err(From::from)
             },
-            Command::Difference(cfg) => cfg.run(),
-            Command::Merge2 { output_file, files } => {
-                let mut files = files.iter();
-                let mut cache = DxvkStateCache::from_file(files.next().unwrap())?;
-                for f in files {
-                    fs::OpenOptions::new()
-                        .read(true)
-                        .open(f)
-                        .map(io::BufReader::new)
-                        .map_err(ReadError::from)
-                        .and_then(|r| cache.append_from(r))?;
+            Command::Difference(cfg) => cfg.run(&options, compress),
+            Command::Merge2 { output_file, files, append, keep_going } => {
+                let mut unmergeable = KeepGoing::new(keep_going, files.len());
+                let mut merged: Option<DxvkStateCache> = None;
+                for f in files.iter() {
+                    let cache = match unmergeable.check(f, DxvkStateCache::from_file(f, &options))? {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    match merged.as_mut() {
+                        Some(merged) => {
+                            let compatible = merged.header.check_compatible(&cache.header).map_err(|e| e.in_file(f));
+                            if unmergeable.check(f, compatible)?.is_some() {
+                                merged.entries.extend(cache.entries);
+                            }
+                        },
+                        None => merged = Some(cache),
+                    }
                 }
-                let f = open_output(output_file)?;
-                cache.write_to(f)?;
-                Ok(())
+                let cache = merged.ok_or(Error::NoEntriesFound)?;
+                let outcome = unmergeable.outcome();
+                if append && compress.is_some() {
+                    return Err(Error::AppendCompressed(output_file).into());
+                }
+                if append {
+                    if let Some(target) = AppendTarget::open(&output_file, &options)? {
+                        target.check_header(&cache.header)?;
+                        let entries = cache.entries.into_iter().map(EntryWrapper::unwrap);
+                        append_entries(target, entries)?;
+                        return outcome.map_err(From::from);
+                    }
+                }
+                write_cache(output_file, &cache, compress)?;
+                outcome.map_err(From::from)
             },
+            Command::Generate(args) => args.run(compress),
+            Command::Locate(args) => args.run(),
+            Command::Where(args) => args.run(),
+            Command::Store(args) => args.run(&options, compress),
+            #[cfg(target_os = "linux")]
+            Command::Watch(args) => args.run(&options),
+            Command::Serve(args) => args.run(&options),
+            Command::Push(args) => args.run(&options),
+            Command::Pull(args) => args.run(&options, compress),
+            Command::Bundle(args) => args.run(&options, compress),
+            Command::Blame(args) => args.run(&options),
+            Command::Similarity(args) => args.run(&options),
+            Command::Show(args) => args.run(&options),
+            #[cfg(feature = "browse")]
+            Command::Browse(args) => args.run(&options, compress),
         }
     })
 }
@@ -0,0 +1,141 @@
+//! Provenance sidecars record where the entries of a merged cache came from, so a bad entry can
+//! be traced back to the tester who contributed it.
+//!
+//! The sidecar of `foo.dxvk-cache` is `foo.dxvk-cache.provenance`. Each line holds an entry's
+//! hash, the file it was merged from, the contributor's label and when it was merged, separated
+//! by tabs. An entry merged from several inputs gets one line per input.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+use crate::{
+    bundle,
+    dxvk::{parse_hash, HashDisplay, Sha1Hash},
+    store,
+};
+
+pub const PROVENANCE_EXTENSION: &str = "provenance";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProvenanceError {
+    #[error("{}: {}", .0.display(), .1)]
+    Io(PathBuf, io::Error),
+    #[error("{}: line {}: invalid provenance record", .0.display(), .1)]
+    Invalid(PathBuf, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub source:      String,
+    pub contributor: String,
+    /// When the entry was merged, as an ISO 8601 UTC timestamp
+    pub time:        String,
+}
+
+impl Record {
+    /// A record of an entry merged from `source` just now
+    pub fn new(source: &Path, contributor: &str) -> Self {
+        Record {
+            source: source.display().to_string(),
+            contributor: contributor.into(),
+            time: bundle::format_timestamp(bundle::unix_time()),
+        }
+    }
+}
+
+/// Tabs and line breaks would split a record, so they're replaced
+fn sanitize(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+/// The sidecar path of the cache at `cache`
+pub fn sidecar_path(cache: &Path) -> PathBuf {
+    let mut path = cache.as_os_str().to_owned();
+    path.push(".");
+    path.push(PROVENANCE_EXTENSION);
+    path.into()
+}
+
+#[derive(Debug, Default)]
+pub struct Provenance {
+    records: HashMap<Sha1Hash, Vec<Record>>,
+}
+
+impl Provenance {
+    /// Loads the sidecar of the cache at `cache`, or `None` if it has none
+    pub fn load(cache: &Path) -> Result<Option<Self>, ProvenanceError> {
+        let path = sidecar_path(cache);
+        let file = match fs::File::open(&path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ProvenanceError::Io(path, e)),
+        };
+        let mut provenance = Provenance::default();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| ProvenanceError::Io(path.clone(), e))?;
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<_> = line.split('\t').collect();
+            let hash = match (fields.len(), fields.first().and_then(|s| parse_hash(s))) {
+                (4, Some(hash)) => hash,
+                _ => return Err(ProvenanceError::Invalid(path, i + 1)),
+            };
+            provenance.add(hash, Record {
+                source: fields[1].into(),
+                contributor: fields[2].into(),
+                time: fields[3].into(),
+            });
+        }
+        Ok(Some(provenance))
+    }
+
+    /// Where the entry with `hash` came from
+    pub fn get(&self, hash: &Sha1Hash) -> &[Record] {
+        self.records.get(hash).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Records that the entry with `hash` came from `record`'s source, unless that's known already
+    pub fn add(&mut self, hash: Sha1Hash, record: Record) {
+        let records = self.records.entry(hash).or_default();
+        if !records.iter().any(|r| r.source == record.source && r.contributor == record.contributor) {
+            records.push(record);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Writes the records sorted by hash
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), io::Error> {
+        let mut hashes: Vec<_> = self.records.keys().collect();
+        hashes.sort_unstable();
+        for hash in hashes {
+            for r in self.records[hash].iter() {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}",
+                    HashDisplay::new(hash),
+                    sanitize(&r.source),
+                    sanitize(&r.contributor),
+                    sanitize(&r.time)
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the sidecar of the cache at `cache`
+    pub fn save(&self, cache: &Path) -> Result<(), ProvenanceError> {
+        let path = sidecar_path(cache);
+        store::write_atomic(&path, |w| self.write_to(w)).map_err(|e| ProvenanceError::Io(path, e))
+    }
+}
@@ -0,0 +1,131 @@
+//! Reports how much each input of a merge contributes, independently of the order they were
+//! merged in
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+use serde::Serialize;
+use crate::dxvk::Sha1Hash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl ReportFormat {
+    /// JSON for `.json` files, text otherwise
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ReportFormat::Json,
+            _ => ReportFormat::Text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InputReport {
+    pub path:    PathBuf,
+    /// Valid entries in the input
+    pub total:   usize,
+    /// Entries no other input has
+    pub unique:  usize,
+    /// Entries at least one other input has as well
+    pub shared:  usize,
+    /// Invalid entries left out of the merge
+    pub omitted: usize,
+}
+
+/// An input left out of the merge because its cache version differs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedInput {
+    pub path:    PathBuf,
+    pub version: NonZeroU32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeReport {
+    pub inputs:  Vec<InputReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedInput>,
+    /// Entries in the merged cache
+    pub merged:  usize,
+}
+
+/// Collects the entries of each input as they're merged
+#[derive(Debug, Default)]
+pub struct ReportBuilder {
+    inputs:  Vec<(PathBuf, HashSet<Sha1Hash>, usize)>,
+    skipped: Vec<SkippedInput>,
+}
+
+impl ReportBuilder {
+    pub fn add_input(&mut self, path: &Path, hashes: HashSet<Sha1Hash>, omitted: usize) {
+        self.inputs.push((path.into(), hashes, omitted));
+    }
+
+    pub fn skip_input(&mut self, path: &Path, version: NonZeroU32) {
+        self.skipped.push(SkippedInput {
+            path: path.into(),
+            version: version,
+        });
+    }
+
+    pub fn build(self) -> MergeReport {
+        let mut counts: HashMap<&Sha1Hash, usize> = HashMap::new();
+        for (_, hashes, _) in self.inputs.iter() {
+            for hash in hashes.iter() {
+                *counts.entry(hash).or_default() += 1;
+            }
+        }
+        let inputs = self.inputs.iter()
+            .map(|(path, hashes, omitted)| {
+                let unique = hashes.iter().filter(|&h| counts[h] == 1).count();
+                InputReport {
+                    path: path.clone(),
+                    total: hashes.len(),
+                    unique: unique,
+                    shared: hashes.len() - unique,
+                    omitted: *omitted,
+                }
+            })
+            .collect();
+        MergeReport {
+            inputs: inputs,
+            skipped: self.skipped,
+            merged: counts.len(),
+        }
+    }
+}
+
+impl MergeReport {
+    pub fn write_to<W: Write>(&self, mut writer: W, format: ReportFormat) -> Result<(), io::Error> {
+        match format {
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writeln!(writer)
+            },
+            ReportFormat::Text => {
+                writeln!(writer, "input\ttotal\tunique\tshared\tomitted")?;
+                for input in self.inputs.iter() {
+                    writeln!(
+                        writer,
+                        "{}\t{}\t{}\t{}\t{}",
+                        input.path.display(),
+                        input.total,
+                        input.unique,
+                        input.shared,
+                        input.omitted
+                    )?;
+                }
+                for input in self.skipped.iter() {
+                    writeln!(writer, "skipped\t{}\tv{}", input.path.display(), input.version)?;
+                }
+                writeln!(writer, "merged\t{}", self.merged)
+            },
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Works out where DXVK keeps a game's state cache, applying the same rules it does at startup:
+//! the cache is named after the executable and lives next to it, unless `DXVK_STATE_CACHE_PATH`
+//! says otherwise, and either the environment or `dxvk.conf` can turn it off.
+
+use std::{
+    collections::HashMap,
+    env,
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+use crate::locate::{
+    self,
+    CACHE_EXTENSION,
+    STATE_CACHE_PATH_VAR,
+};
+
+/// Environment variable that disables the state cache when set to `0`
+pub const STATE_CACHE_VAR: &str = "DXVK_STATE_CACHE";
+/// Environment variable pointing DXVK at its configuration file
+pub const CONFIG_FILE_VAR: &str = "DXVK_CONFIG_FILE";
+/// Environment variable holding configuration options, separated by `;`
+pub const CONFIG_VAR: &str = "DXVK_CONFIG";
+pub const CONFIG_FILE_NAME: &str = "dxvk.conf";
+const ENABLE_OPTION: &str = "dxvk.enableStateCache";
+
+pub type Environment = HashMap<String, String>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResolveError {
+    #[error("{}: {}", .0.display(), .1)]
+    Io(PathBuf, io::Error),
+    #[error("{}: not an executable's path", .0.display())]
+    NoFileName(PathBuf),
+    #[error("{var}={value}: Windows path outside of a Wine prefix")]
+    UnmappablePath {
+        var:   &'static str,
+        value: String,
+    },
+}
+
+/// What turned the state cache off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Disabled {
+    Environment,
+    /// Disabled by a configuration file, or by `DXVK_CONFIG` if there's no path
+    Config(Option<PathBuf>),
+}
+
+impl fmt::Display for Disabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Disabled::Environment => write!(f, "{}=0", STATE_CACHE_VAR),
+            Disabled::Config(Some(path)) => write!(f, "{} = False in {}", ENABLE_OPTION, path.display()),
+            Disabled::Config(None) => write!(f, "{} = False in {}", ENABLE_OPTION, CONFIG_VAR),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub path:     PathBuf,
+    /// The configuration file DXVK would read, if it exists
+    pub config:   Option<PathBuf>,
+    pub disabled: Option<Disabled>,
+}
+
+impl Resolution {
+    #[inline]
+    pub fn enabled(&self) -> bool {
+        self.disabled.is_none()
+    }
+}
+
+/// The variables of the current process that are valid Unicode
+pub fn current_environment() -> Environment {
+    env::vars_os()
+        .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+        .collect()
+}
+
+/// Parses `KEY=VALUE` lines, as written by launchers or shell scripts. Blank lines, comments and
+/// an `export` prefix are skipped, and values may be quoted.
+pub fn parse_env_file(src: &str) -> Environment {
+    src.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.strip_prefix("export ").unwrap_or(line))
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim().to_owned(), unquote(v.trim()).to_owned()))
+        .collect()
+}
+
+pub fn read_env_file(path: &Path) -> Result<Environment, ResolveError> {
+    fs::read_to_string(path)
+        .map(|s| parse_env_file(&s))
+        .map_err(|e| ResolveError::Io(path.into(), e))
+}
+
+fn unquote(s: &str) -> &str {
+    for quote in ['"', '\''].iter() {
+        if let Some(s) = s.strip_prefix(*quote).and_then(|s| s.strip_suffix(*quote)) {
+            return s;
+        }
+    }
+    s
+}
+
+/// The value `dxvk.enableStateCache` ends up with for `exe_name` in a configuration file.
+/// Options before the first section apply to every executable, those in an `[exe]` section only
+/// to that one.
+fn config_option(src: &str, exe_name: &str) -> Option<bool> {
+    let mut active = true;
+    let mut value = None;
+    for line in src.lines().map(str::trim) {
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            active = section.eq_ignore_ascii_case(exe_name);
+            continue;
+        }
+        if active {
+            value = option_value(line).or(value);
+        }
+    }
+    value
+}
+
+/// Parses a single `key = value` option, if it's the one that enables the state cache
+fn option_value(option: &str) -> Option<bool> {
+    let (key, value) = option.split_once('=')?;
+    if key.trim() != ENABLE_OPTION {
+        return None;
+    }
+    match unquote(value.trim()) {
+        v if v.eq_ignore_ascii_case("true") => Some(true),
+        v if v.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+/// The Wine prefix an executable is installed in, i.e. the parent of its `drive_c`
+fn wine_prefix(exe: &Path) -> Option<&Path> {
+    exe.ancestors()
+        .find(|p| p.file_name().is_some_and(|name| name == "drive_c"))
+        .and_then(Path::parent)
+}
+
+/// Maps a path taken from the environment to the host, relative to the executable's directory
+fn host_path(var: &'static str, value: &str, exe: &Path) -> Result<PathBuf, ResolveError> {
+    let exe_dir = exe.parent().unwrap_or_else(|| Path::new(""));
+    match locate::resolve_wine_path(value, wine_prefix(exe)) {
+        Some(path) => Ok(path),
+        None if value.get(1..2) == Some(":") => Err(ResolveError::UnmappablePath {
+            var: var,
+            value: value.into(),
+        }),
+        None => Ok(exe_dir.join(value)),
+    }
+}
+
+/// Resolves the state cache of `exe`. The configuration file is `config` if given, otherwise the
+/// one `DXVK_CONFIG_FILE` names or `dxvk.conf` in the executable's directory, which Wine
+/// launchers use as the working directory.
+pub fn resolve(exe: &Path, env: &Environment, config: Option<&Path>) -> Result<Resolution, ResolveError> {
+    let exe_name = exe.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| ResolveError::NoFileName(exe.into()))?;
+    let mut file_name = exe.file_stem().unwrap().to_os_string();
+    file_name.push(".");
+    file_name.push(CACHE_EXTENSION);
+
+    let dir = match env.get(STATE_CACHE_PATH_VAR).filter(|v| !v.is_empty()) {
+        Some(value) => host_path(STATE_CACHE_PATH_VAR, value, exe)?,
+        None => exe.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+
+    let config = match (config, env.get(CONFIG_FILE_VAR).filter(|v| !v.is_empty())) {
+        (Some(path), _) => Some(path.to_path_buf()),
+        (None, Some(value)) => Some(host_path(CONFIG_FILE_VAR, value, exe)?),
+        (None, None) => exe.parent().map(|dir| dir.join(CONFIG_FILE_NAME)),
+    };
+    let config = config.filter(|p| p.is_file());
+    let mut disabled = None;
+    if let Some(path) = config.as_ref() {
+        let src = fs::read_to_string(path).map_err(|e| ResolveError::Io(path.clone(), e))?;
+        if config_option(&src, exe_name) == Some(false) {
+            disabled = Some(Disabled::Config(Some(path.clone())));
+        }
+    }
+    // Options in DXVK_CONFIG take precedence over the configuration file
+    if let Some(value) = env.get(CONFIG_VAR).and_then(|v| v.rsplit(';').find_map(option_value)) {
+        disabled = if value { None } else { Some(Disabled::Config(None)) };
+    }
+    if env.get(STATE_CACHE_VAR).map(String::as_str) == Some("0") {
+        disabled = Some(Disabled::Environment);
+    }
+
+    Ok(Resolution {
+        path: dir.join(file_name),
+        config: config,
+        disabled: disabled,
+    })
+}
@@ -0,0 +1,245 @@
+//! HTTP API exposing a [`Store`], so one machine can act as the cache hub for others.
+//!
+//! - `GET /games` lists the stored games as JSON
+//! - `GET /games/<game>` returns a game's cache
+//! - `PUT /games/<game>` merges the uploaded cache into the game's
+//!
+//! A few requests are served at once; concurrent merges into the store wait for its lock.
+
+use std::{
+    error::Error as StdError,
+    io::{self, Cursor, Read},
+    net::SocketAddr,
+    path::Path,
+    sync::Mutex,
+    thread,
+};
+use log::{info, warn};
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, StatusCode};
+use crate::{
+    compress,
+    dxvk::{DxvkStateCache, ReadError, ReadOptions},
+    store::{Store, StoreError},
+};
+
+pub const GAMES_PATH: &str = "/games";
+/// Largest request body accepted by default. Uploads are held in memory while being parsed, by
+/// each of the workers at once.
+pub const MAX_UPLOAD_SIZE: u64 = 64 << 20;
+/// How many requests are served at once
+const WORKERS: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServeError {
+    #[error("Cannot listen on {0}: {1}")]
+    Bind(String, Box<dyn StdError + Send + Sync + 'static>),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).unwrap()
+}
+
+fn json_response(status: u16, value: serde_json::Value) -> HttpResponse {
+    Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn error_response(status: u16, message: &str) -> HttpResponse {
+    json_response(status, json!({ "error": message }))
+}
+
+fn store_error_response(e: &StoreError) -> HttpResponse {
+    let status = match e {
+        StoreError::NotFound(..) => 404,
+        StoreError::InvalidGame(..) => 400,
+        StoreError::Read(e) if matches!(e.inner(), ReadError::VersionMismatch { .. } | ReadError::EntrySizeMismatch { .. }) => 409,
+        _ => 500,
+    };
+    error_response(status, &e.to_string())
+}
+
+/// Decodes `%XX` escapes, as used for game identities in URLs
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Escapes everything but unreserved characters, so any game identity fits in a path segment
+pub(crate) fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+pub struct Server {
+    http:    tiny_http::Server,
+    store:   Store,
+    options: ReadOptions,
+    max_upload_size: u64,
+}
+
+impl Server {
+    /// Listens on `addr`. Uploads are parsed with `options` before being merged into `store`.
+    pub fn bind(addr: &str, store: Store, options: ReadOptions) -> Result<Self, ServeError> {
+        Self::bind_with_upload_limit(addr, store, options, MAX_UPLOAD_SIZE)
+    }
+
+    /// Like [`Server::bind`], refusing request bodies larger than `max_upload_size` bytes. The
+    /// entries of a compressed upload can't add up to more than that either.
+    pub fn bind_with_upload_limit(
+        addr: &str,
+        store: Store,
+        mut options: ReadOptions,
+        max_upload_size: u64,
+    ) -> Result<Self, ServeError> {
+        let http = tiny_http::Server::http(addr).map_err(|e| ServeError::Bind(addr.into(), e))?;
+        options.limits.max_cache_size = options.limits.max_cache_size.min(max_upload_size);
+        Ok(Server {
+            http: http,
+            store: store,
+            options: options,
+            max_upload_size: max_upload_size,
+        })
+    }
+
+    #[inline]
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Serves requests on a few threads, until accepting a connection fails
+    pub fn run(&self) -> Result<(), ServeError> {
+        let failure = Mutex::new(None);
+        thread::scope(|scope| {
+            for _ in 0..WORKERS {
+                scope.spawn(|| {
+                    if let Err(e) = self.serve() {
+                        failure.lock().unwrap().get_or_insert(e);
+                        // Wakes up another worker, which then stops the same way
+                        self.http.unblock();
+                    }
+                });
+            }
+        });
+        match failure.into_inner().unwrap() {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn serve(&self) -> Result<(), io::Error> {
+        loop {
+            let mut request = self.http.recv()?;
+            let response = self.handle(&mut request);
+            info!("{} {} {}", request.method(), request.url(), response.status_code().0);
+            if let Err(e) = request.respond(response) {
+                warn!("Error sending response: {}", e);
+            }
+        }
+    }
+
+    fn handle(&self, request: &mut Request) -> HttpResponse {
+        let url = request.url().split('?').next().unwrap_or("").to_owned();
+        if url == GAMES_PATH {
+            return match request.method() {
+                Method::Get => self.list(),
+                _ => error_response(405, "Method not allowed"),
+            };
+        }
+        let game = match url.strip_prefix(GAMES_PATH).and_then(|s| s.strip_prefix('/')).and_then(percent_decode) {
+            Some(v) => v,
+            None => return error_response(404, "Not found"),
+        };
+        match request.method() {
+            Method::Get => self.get(&game),
+            Method::Put | Method::Post => self.upload(&game, request),
+            _ => error_response(405, "Method not allowed"),
+        }
+    }
+
+    fn list(&self) -> HttpResponse {
+        match self.store.list() {
+            Ok(games) => {
+                let games: Vec<_> = games.iter()
+                    .map(|g| json!({ "game": g.game, "version": g.version.get(), "entries": g.entries }))
+                    .collect();
+                json_response(200, json!(games))
+            },
+            Err(e) => store_error_response(&e),
+        }
+    }
+
+    fn get(&self, game: &str) -> HttpResponse {
+        let cache = match self.store.get(game) {
+            Ok(v) => v,
+            Err(e) => return store_error_response(&e),
+        };
+        let mut body = Vec::new();
+        if let Err(e) = cache.write_to(&mut body) {
+            return error_response(500, &e.to_string());
+        }
+        Response::from_data(body)
+            .with_status_code(StatusCode(200))
+            .with_header(header("Content-Type", "application/octet-stream"))
+    }
+
+    /// Parses the upload completely before touching the store, so invalid caches are rejected
+    /// without merging any of their entries. Bodies larger than the upload limit are refused
+    /// without being read.
+    fn upload(&self, game: &str, request: &mut Request) -> HttpResponse {
+        let limit = self.max_upload_size;
+        let too_large = || error_response(413, &format!("Uploads are limited to {} bytes", limit));
+        if request.body_length().is_some_and(|len| len as u64 > limit) {
+            return too_large();
+        }
+        let mut body = Vec::new();
+        if let Err(e) = request.as_reader().take(limit + 1).read_to_end(&mut body) {
+            return error_response(400, &e.to_string());
+        }
+        if body.len() as u64 > limit {
+            return too_large();
+        }
+        let reader = match compress::decompress(Cursor::new(body)) {
+            Ok(v) => v,
+            Err(e) => return error_response(400, &e.to_string()),
+        };
+        let cache = match DxvkStateCache::from_reader_with(reader, &self.options) {
+            Ok(v) => v,
+            Err(e) => return error_response(400, &format!("Invalid state cache: {}", e)),
+        };
+        if cache.entries.is_empty() {
+            return error_response(400, "No valid state cache entries found");
+        }
+        match self.store.add_cache(game, &cache, Path::new("upload")) {
+            Ok(summary) => json_response(200, json!({ "added": summary.added, "total": summary.total })),
+            Err(e) => store_error_response(&e),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Describes single entries of a state cache, along with where they're stored
+
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{self, Read, Write},
+};
+use crate::dxvk::{
+    DxvkStateCacheEntry,
+    DxvkStateCacheHeader,
+    EntryPosition,
+    EntryReader,
+    HashPrefix,
+    ReadError,
+    ReadOptions,
+};
+
+/// Bytes shown per line of a [`Hexdump`]
+pub const HEXDUMP_WIDTH: usize = 16;
+
+/// An entry along with where it starts in its cache
+#[derive(Debug, Clone)]
+pub struct LocatedEntry {
+    pub position: EntryPosition,
+    pub entry:    DxvkStateCacheEntry,
+    /// Whether the entry matches its hash
+    pub valid:    bool,
+}
+
+/// Reads the entries of a cache in the order they're stored, unlike
+/// [`DxvkStateCache`](crate::dxvk::DxvkStateCache) which only keeps the set of them. Entries that
+/// don't match their hash are kept too, so damaged caches can be looked into.
+pub fn read_entries<R>(mut reader: R, options: &ReadOptions) -> Result<(DxvkStateCacheHeader, Vec<LocatedEntry>), ReadError>
+where
+    R: Read,
+{
+    let header = DxvkStateCacheHeader::from_reader_with(&mut reader, options)?;
+    let mut reader = EntryReader::new(reader, header, options.limits);
+    let mut hashes = HashSet::new();
+    let mut entries = Vec::new();
+    while let Some(e) = reader.next_entry_unverified().map_err(|e| ReadError::ReadEntry(reader.position(), e))? {
+        if !hashes.insert(e.hash) {
+            return Err(ReadError::DuplicateEntry(reader.position()));
+        }
+        entries.push(LocatedEntry {
+            position: reader.position(),
+            valid: e.is_valid(),
+            entry: e,
+        });
+    }
+    Ok((header, entries))
+}
+
+/// Entries whose hash starts with `prefix`
+pub fn find<'a>(entries: &'a [LocatedEntry], prefix: &'a HashPrefix) -> impl Iterator<Item=&'a LocatedEntry> + 'a {
+    entries.iter().filter(move |e| prefix.matches(&e.entry.hash))
+}
+
+/// Shows bytes the way `hexdump -C` does, offsets counted from the first of them
+pub struct Hexdump<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Display for Hexdump<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.0.chunks(HEXDUMP_WIDTH).enumerate() {
+            write!(f, "{:08x} ", i * HEXDUMP_WIDTH)?;
+            for j in 0..HEXDUMP_WIDTH {
+                if j % 8 == 0 {
+                    write!(f, " ")?;
+                }
+                match line.get(j) {
+                    Some(b) => write!(f, "{:02x} ", b)?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            write!(f, " |")?;
+            for &b in line {
+                let c = if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f, "|")?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints everything known about `entry`, followed by a hexdump of its data. The pipeline state
+/// in the data isn't decoded, since its layout changes with nearly every cache version.
+pub fn write_details<W: Write>(mut writer: W, header: &DxvkStateCacheHeader, entry: &LocatedEntry) -> Result<(), io::Error> {
+    let e = &entry.entry;
+    writeln!(writer, "hash: {}", e.hash_display())?;
+    if !entry.valid {
+        writeln!(writer, "invalid: the data doesn't match the hash")?;
+    }
+    writeln!(writer, "index: {}", entry.position.index)?;
+    writeln!(writer, "offset: {:#x}", entry.position.offset)?;
+    writeln!(writer, "size: {} bytes, {} of them data", e.size_on_disk(), e.data.len())?;
+    match e.header {
+        Some(h) => writeln!(writer, "stages: {} ({:#04x})", h.stages(), h.stage_mask)?,
+        None => writeln!(writer, "stages: not stored by v{} caches", header.version)?,
+    }
+    writeln!(writer, "data:")?;
+    write!(writer, "{}", Hexdump(&e.data))
+}
@@ -0,0 +1,70 @@
+//! Compares the entry sets of caches, to tell which ones are worth merging
+
+use std::collections::HashSet;
+use crate::dxvk::Sha1Hash;
+
+/// Entries in both sets relative to entries in either
+pub fn jaccard(a: &HashSet<Sha1Hash>, b: &HashSet<Sha1Hash>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// How much of `a` is contained in `b`
+pub fn containment(a: &HashSet<Sha1Hash>, b: &HashSet<Sha1Hash>) -> f64 {
+    if a.is_empty() {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / a.len() as f64
+}
+
+/// `measure` applied to every pair of sets, row by row
+pub fn matrix<F>(sets: &[HashSet<Sha1Hash>], measure: F) -> Vec<Vec<f64>>
+where
+    F: Fn(&HashSet<Sha1Hash>, &HashSet<Sha1Hash>) -> f64,
+{
+    sets.iter()
+        .map(|a| sets.iter().map(|b| measure(a, b)).collect())
+        .collect()
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Groups sets whose Jaccard similarity reaches `threshold`, directly or through other sets in
+/// the group. Each cluster lists its sets largest first, so the first one is the best single
+/// pick, and clusters are ordered by their first set.
+pub fn clusters(sets: &[HashSet<Sha1Hash>], threshold: f64) -> Vec<Vec<usize>> {
+    let mut parents: Vec<usize> = (0..sets.len()).collect();
+    for i in 0..sets.len() {
+        for j in i + 1..sets.len() {
+            if jaccard(&sets[i], &sets[j]) >= threshold {
+                let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut roots = Vec::new();
+    for i in 0..sets.len() {
+        let root = find(&mut parents, i);
+        match roots.iter().position(|&r| r == root) {
+            Some(c) => clusters[c].push(i),
+            None => {
+                roots.push(root);
+                clusters.push(vec![i]);
+            },
+        }
+    }
+    for cluster in clusters.iter_mut() {
+        cluster.sort_by_key(|&i| std::cmp::Reverse(sets[i].len()));
+    }
+    clusters
+}
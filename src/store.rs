@@ -0,0 +1,399 @@
+//! A directory holding one canonical state cache per game, which caches from players get merged
+//! into.
+//!
+//! Entries are stored once, no matter how many games share them: `objects/ab/cdef…` holds the
+//! entry with hash `abcdef…`, encoded the way its cache version writes it. Each game has a
+//! manifest in `manifests/<game>`, a line with the version and entry size of its cache followed
+//! by the hash of every entry, one per line.
+//!
+//! Changes to the store take an exclusive lock on its `lock` file first, so several processes
+//! adding to or cleaning up the same store don't interleave their writes.
+
+use std::{
+    collections::HashSet,
+    env,
+    fs,
+    io::{self, BufReader, BufWriter, Write},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+use log::warn;
+use crate::dxvk::{
+    parse_hash,
+    read_valid_entries,
+    DxvkStateCache,
+    DxvkStateCacheEntry,
+    DxvkStateCacheHeader,
+    EntryError,
+    EntryWrapper,
+    HashDisplay,
+    ReadError,
+    ReadOptions,
+    Sha1Hash,
+};
+
+/// The store's directory below `$XDG_DATA_HOME`
+const STORE_DIR: &str = "dxvk-cache-tool/store";
+const OBJECTS_DIR: &str = "objects";
+const MANIFESTS_DIR: &str = "manifests";
+const TMP_EXTENSION: &str = "tmp";
+const LOCK_FILE: &str = "lock";
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("{}: {}", .0.display(), .1)]
+    Io(PathBuf, io::Error),
+    #[error("{0}")]
+    Read(ReadError),
+    #[error("{}: {}", .0.display(), .1)]
+    Object(PathBuf, EntryError),
+    #[error("{}: object doesn't match its hash", .0.display())]
+    ObjectMismatch(PathBuf),
+    #[error("{}:{}: invalid manifest line", .0.display(), .1)]
+    InvalidManifest(PathBuf, usize),
+    #[error("Invalid game identity \"{0}\"")]
+    InvalidGame(String),
+    #[error("No cache stored for \"{0}\"")]
+    NotFound(String),
+    #[error("Cannot determine the data directory; set $XDG_DATA_HOME or $HOME, or pass --store")]
+    NoDataDir,
+}
+
+/// A game's cache, as reported by [`Store::list`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredGame {
+    pub game:    String,
+    pub version: NonZeroU32,
+    pub entries: usize,
+}
+
+/// How [`Store::add`] changed a game's cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddSummary {
+    pub added: usize,
+    pub total: usize,
+}
+
+/// What [`Store::gc`] cleaned up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcSummary {
+    pub removed: usize,
+    pub freed:   u64,
+    pub kept:    usize,
+}
+
+/// The header and entry hashes of a stored cache
+#[derive(Debug)]
+struct Manifest {
+    header: DxvkStateCacheHeader,
+    hashes: Vec<Sha1Hash>,
+}
+
+impl Manifest {
+    fn parse(path: &Path, src: &str) -> Result<Self, StoreError> {
+        let invalid = |line: usize| StoreError::InvalidManifest(path.into(), line + 1);
+        let mut lines = src.lines().enumerate();
+        let header = lines.next()
+            .and_then(|(_, line)| {
+                let (version, entry_size) = line.strip_prefix('v')?.split_once(' ')?;
+                Some(DxvkStateCacheHeader::new(version.parse().ok()?, entry_size.parse().ok()?))
+            })
+            .ok_or_else(|| invalid(0))?;
+        let hashes = lines
+            .map(|(i, line)| parse_hash(line).ok_or_else(|| invalid(i)))
+            .collect::<Result<_, _>>()?;
+        Ok(Manifest {
+            header: header,
+            hashes: hashes,
+        })
+    }
+
+    fn write_to<W: Write>(&self, mut writer: W) -> Result<(), io::Error> {
+        writeln!(writer, "v{} {}", self.header.version, self.header.entry_size)?;
+        for hash in self.hashes.iter() {
+            writeln!(writer, "{}", HashDisplay::new(hash))?;
+        }
+        Ok(())
+    }
+}
+
+/// Game identities become file names, so they can't contain separators, start with a dot or
+/// look like a temporary file
+pub(crate) fn is_valid_game(game: &str) -> bool {
+    let tmp = Path::new(game).extension().is_some_and(|ext| ext == TMP_EXTENSION);
+    !(game.is_empty() || game.starts_with('.') || game.contains(['/', '\\', '\0']) || tmp)
+}
+
+/// Writes to a temporary file next to `path` first, so an interrupted write never leaves a
+/// partial file behind
+pub(crate) fn write_atomic<F>(path: &Path, write: F) -> Result<(), io::Error>
+where
+    F: FnOnce(&mut BufWriter<fs::File>) -> Result<(), io::Error>,
+{
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".");
+    tmp.push(TMP_EXTENSION);
+    let tmp = PathBuf::from(tmp);
+    let result = fs::File::create(&tmp)
+        .map(BufWriter::new)
+        .and_then(|mut writer| {
+            write(&mut writer)?;
+            writer.flush()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[derive(Debug)]
+pub struct Store {
+    root:    PathBuf,
+    options: ReadOptions,
+}
+
+impl Store {
+    /// `$XDG_DATA_HOME/dxvk-cache-tool/store`, falling back to `~/.local/share`
+    pub fn default_root() -> Result<PathBuf, StoreError> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").filter(|v| !v.is_empty()).map(|h| Path::new(&h).join(".local/share")))
+            .ok_or(StoreError::NoDataDir)?;
+        Ok(data_home.join(STORE_DIR))
+    }
+
+    /// Opens the store at `root`, creating it if needed. `options` apply to reading the caches
+    /// added to it as well as the stored ones.
+    pub fn open(root: PathBuf, options: ReadOptions) -> Result<Self, StoreError> {
+        for dir in [OBJECTS_DIR, MANIFESTS_DIR].iter() {
+            let dir = root.join(dir);
+            fs::create_dir_all(&dir).map_err(|e| StoreError::Io(dir, e))?;
+        }
+        Ok(Store {
+            root: root,
+            options: options,
+        })
+    }
+
+    /// Waits for an exclusive lock on the store, which is held until the returned file is
+    /// dropped
+    fn lock(&self) -> Result<fs::File, StoreError> {
+        let path = self.root.join(LOCK_FILE);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| StoreError::Io(path.clone(), e))?;
+        file.lock().map_err(|e| StoreError::Io(path, e))?;
+        Ok(file)
+    }
+
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, dir: &Path) -> Result<Vec<PathBuf>, StoreError> {
+        let io_error = |e| StoreError::Io(dir.into(), e);
+        let mut paths = fs::read_dir(dir)
+            .map_err(io_error)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(io_error)?;
+        paths.sort();
+        Ok(paths)
+    }
+
+    fn check_game(game: &str) -> Result<(), StoreError> {
+        if !is_valid_game(game) {
+            return Err(StoreError::InvalidGame(game.into()));
+        }
+        Ok(())
+    }
+
+    fn manifest_path(&self, game: &str) -> PathBuf {
+        self.root.join(MANIFESTS_DIR).join(game)
+    }
+
+    fn object_path(&self, hash: &Sha1Hash) -> PathBuf {
+        let hex = HashDisplay::new(hash).to_string();
+        self.root.join(OBJECTS_DIR).join(&hex[..2]).join(&hex[2..])
+    }
+
+    fn load_manifest(&self, game: &str) -> Result<Option<Manifest>, StoreError> {
+        Self::check_game(game)?;
+        let path = self.manifest_path(game);
+        match fs::read_to_string(&path) {
+            Ok(src) => Manifest::parse(&path, &src).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StoreError::Io(path, e)),
+        }
+    }
+
+    fn load_object(&self, hash: &Sha1Hash, header: &DxvkStateCacheHeader) -> Result<DxvkStateCacheEntry, StoreError> {
+        let path = self.object_path(hash);
+        let reader = fs::File::open(&path)
+            .map(BufReader::new)
+            .map_err(|e| StoreError::Io(path.clone(), e))?;
+        let entry = DxvkStateCacheEntry::from_reader(reader, header, &self.options.limits)
+            .map_err(|e| StoreError::Object(path.clone(), e))?;
+        if entry.hash != *hash {
+            return Err(StoreError::ObjectMismatch(path));
+        }
+        Ok(entry)
+    }
+
+    /// The stored cache of `game`, if there is one
+    fn load(&self, game: &str) -> Result<Option<DxvkStateCache>, StoreError> {
+        let manifest = match self.load_manifest(game)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let entries = manifest.hashes.iter()
+            .map(|hash| self.load_object(hash, &manifest.header).map(EntryWrapper::from))
+            .collect::<Result<_, _>>()?;
+        Ok(Some(DxvkStateCache {
+            header: manifest.header,
+            entries: entries,
+        }))
+    }
+
+    /// Writes the objects `cache` is missing from the store, then its manifest
+    fn save(&self, game: &str, cache: &DxvkStateCache) -> Result<(), StoreError> {
+        let edition = cache.header.edition();
+        for entry in cache.iter() {
+            let path = self.object_path(&entry.hash);
+            if path.is_file() {
+                continue;
+            }
+            let dir = path.parent().unwrap();
+            fs::create_dir_all(dir).map_err(|e| StoreError::Io(dir.into(), e))?;
+            write_atomic(&path, |w| entry.write_to(w, edition)).map_err(|e| StoreError::Io(path, e))?;
+        }
+        let mut hashes: Vec<_> = cache.iter().map(|e| e.hash).collect();
+        hashes.sort_unstable();
+        let manifest = Manifest {
+            header: DxvkStateCacheHeader::new(cache.header.version, cache.header.entry_size),
+            hashes: hashes,
+        };
+        let path = self.manifest_path(game);
+        write_atomic(&path, |w| manifest.write_to(w)).map_err(|e| StoreError::Io(path, e))
+    }
+
+    /// Merges the cache at `input` into the one stored for `game`. Entries that don't match their
+    /// hash are left out, the way `merge` leaves them out.
+    pub fn add(&self, game: &str, input: &Path) -> Result<AddSummary, StoreError> {
+        let cache = self.read_input(input)?;
+        self.add_cache(game, &cache, input)
+    }
+
+    /// Merges `cache`, read from `source`, into the one stored for `game`
+    pub fn add_cache(&self, game: &str, cache: &DxvkStateCache, source: &Path) -> Result<AddSummary, StoreError> {
+        let _lock = self.lock()?;
+        self.merge(game, cache, source)
+    }
+
+    fn read_input(&self, input: &Path) -> Result<DxvkStateCache, StoreError> {
+        let (header, entries, omitted) = read_valid_entries(input, &self.options)
+            .map_err(StoreError::Read)?;
+        if omitted > 0 {
+            warn!("{}: {} entries are omitted as invalid", input.display(), omitted);
+        }
+        Ok(DxvkStateCache {
+            header: header,
+            entries: entries.into_iter().map(EntryWrapper::from).collect(),
+        })
+    }
+
+    /// Merges `cache` into the one stored for `game`, with the lock already held
+    fn merge(&self, game: &str, cache: &DxvkStateCache, source: &Path) -> Result<AddSummary, StoreError> {
+        let stored = self.load(game)?;
+        let before = stored.as_ref().map_or(0, |c| c.entries.len());
+        let merged_with_stored;
+        let merged = match stored {
+            Some(stored) => {
+                merged_with_stored = stored.merge(cache).map_err(|e| StoreError::Read(e.in_file(source)))?;
+                &merged_with_stored
+            },
+            None => cache,
+        };
+        self.save(game, merged)?;
+        Ok(AddSummary {
+            added: merged.entries.len() - before,
+            total: merged.entries.len(),
+        })
+    }
+
+    pub fn get(&self, game: &str) -> Result<DxvkStateCache, StoreError> {
+        self.load(game)?.ok_or_else(|| StoreError::NotFound(game.into()))
+    }
+
+    fn games(&self) -> Result<Vec<String>, StoreError> {
+        let games = self.read_dir(&self.root.join(MANIFESTS_DIR))?
+            .into_iter()
+            .filter(|p| p.extension().is_none_or(|ext| ext != TMP_EXTENSION))
+            .filter_map(|p| p.file_name().and_then(|s| s.to_str()).map(String::from))
+            .collect();
+        Ok(games)
+    }
+
+    /// Every stored game, sorted by identity
+    pub fn list(&self) -> Result<Vec<StoredGame>, StoreError> {
+        let mut games = Vec::new();
+        for game in self.games()? {
+            let manifest = self.load_manifest(&game)?
+                .ok_or_else(|| StoreError::NotFound(game.clone()))?;
+            games.push(StoredGame {
+                game: game,
+                version: manifest.header.version,
+                entries: manifest.hashes.len(),
+            });
+        }
+        Ok(games)
+    }
+
+    /// Removes a game's manifest. Its entries stay in the store until [`Store::gc`] runs.
+    pub fn remove(&self, game: &str) -> Result<(), StoreError> {
+        Self::check_game(game)?;
+        let _lock = self.lock()?;
+        let path = self.manifest_path(game);
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(StoreError::NotFound(game.into())),
+            Err(e) => Err(StoreError::Io(path, e)),
+        }
+    }
+
+    /// Deletes the objects no manifest references, along with leftovers of interrupted writes
+    pub fn gc(&self) -> Result<GcSummary, StoreError> {
+        let _lock = self.lock()?;
+        let mut referenced = HashSet::new();
+        for game in self.games()? {
+            if let Some(manifest) = self.load_manifest(&game)? {
+                referenced.extend(manifest.hashes);
+            }
+        }
+        let mut summary = GcSummary::default();
+        for dir in self.read_dir(&self.root.join(OBJECTS_DIR))? {
+            for path in self.read_dir(&dir)? {
+                let hash = dir.file_name()
+                    .zip(path.file_name())
+                    .and_then(|(a, b)| parse_hash(&format!("{}{}", a.to_str()?, b.to_str()?)));
+                if hash.is_some_and(|hash| referenced.contains(&hash)) {
+                    summary.kept += 1;
+                    continue;
+                }
+                let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+                fs::remove_file(&path).map_err(|e| StoreError::Io(path.clone(), e))?;
+                summary.removed += 1;
+                summary.freed += size;
+            }
+            // Only succeeds once the directory is empty
+            let _ = fs::remove_dir(&dir);
+        }
+        Ok(summary)
+    }
+}
@@ -0,0 +1,204 @@
+//! Watches the directories games write their state caches to, and merges each cache once it has
+//! stopped changing, i.e. the game exited or hasn't compiled a new pipeline for a while
+
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+use inotify::{
+    EventMask,
+    Inotify,
+    WatchDescriptor,
+    WatchMask,
+};
+use log::{debug, info, warn};
+use crate::{
+    dxvk::{read_valid_entries, DxvkStateCache, EntryWrapper, ReadError, ReadOptions},
+    locate::CACHE_EXTENSION,
+    store::{self, AddSummary, Store, StoreError},
+};
+
+/// How often to check for caches that have gone idle
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatchError {
+    #[error("{}: {}", .0.display(), .1)]
+    Io(PathBuf, io::Error),
+    /// Setting up inotify, or reading its events, failed
+    #[error("inotify: {0}")]
+    Inotify(io::Error),
+    #[error("{0}")]
+    Read(#[from] ReadError),
+    #[error("{0}")]
+    Store(#[from] StoreError),
+}
+
+/// Where finished caches get merged into
+#[derive(Debug)]
+pub enum Sink {
+    /// The store, under the identity of the cache's file name
+    Store(Store),
+    /// A single master cache
+    File(PathBuf),
+}
+
+impl Sink {
+    fn merge_into_file(master: &Path, input: &Path, options: &ReadOptions) -> Result<AddSummary, WatchError> {
+        let (header, entries, omitted) = read_valid_entries(input, options)?;
+        if omitted > 0 {
+            warn!("{}: {} entries are omitted as invalid", input.display(), omitted);
+        }
+        let cache = DxvkStateCache {
+            header: header,
+            entries: entries.into_iter().map(EntryWrapper::from).collect(),
+        };
+        let (merged, before) = match DxvkStateCache::from_file(master, options) {
+            Ok(stored) => {
+                let merged = stored.merge(&cache).map_err(|e| e.in_file(input))?;
+                (merged, stored.entries.len())
+            },
+            Err(e) if matches!(e.inner(), ReadError::Io(e) if e.kind() == io::ErrorKind::NotFound) => (cache, 0),
+            Err(e) => return Err(e.into()),
+        };
+        if merged.entries.len() > before {
+            store::write_atomic(master, |w| merged.write_to(w))
+                .map_err(|e| WatchError::Io(master.into(), e))?;
+        }
+        Ok(AddSummary {
+            added: merged.entries.len() - before,
+            total: merged.entries.len(),
+        })
+    }
+
+    pub fn merge(&self, input: &Path, options: &ReadOptions) -> Result<AddSummary, WatchError> {
+        match self {
+            Sink::Store(store) => {
+                let game = input.file_stem().unwrap_or_default().to_string_lossy();
+                Ok(store.add(&game, input)?)
+            },
+            Sink::File(master) => Self::merge_into_file(master, input, options),
+        }
+    }
+
+    /// Whether `path` is the master file, which mustn't be merged into itself
+    fn is_master(&self, path: &Path) -> bool {
+        match self {
+            Sink::Store(..) => false,
+            Sink::File(master) => match (fs::canonicalize(master), fs::canonicalize(path)) {
+                (Ok(master), Ok(path)) => master == path,
+                _ => false,
+            },
+        }
+    }
+}
+
+pub struct Watcher {
+    inotify: Inotify,
+    dirs:    HashMap<WatchDescriptor, PathBuf>,
+    /// Caches that changed, and when they last did
+    pending: HashMap<PathBuf, Instant>,
+    idle:    Duration,
+}
+
+impl Watcher {
+    /// Watches `dirs` for changes to state caches, considering a cache finished once it hasn't
+    /// changed for `idle`
+    pub fn new<'a, It>(dirs: It, idle: Duration) -> Result<Self, WatchError>
+    where
+        It: IntoIterator<Item=&'a Path>,
+    {
+        let inotify = Inotify::init().map_err(WatchError::Inotify)?;
+        let mask = WatchMask::MODIFY | WatchMask::CLOSE_WRITE | WatchMask::CREATE
+            | WatchMask::MOVED_TO | WatchMask::DELETE | WatchMask::MOVED_FROM;
+        let mut watched = HashMap::new();
+        for dir in dirs {
+            let wd = inotify.watches()
+                .add(dir, mask)
+                .map_err(|e| WatchError::Io(dir.into(), e))?;
+            info!("Watching {}", dir.display());
+            watched.insert(wd, dir.to_path_buf());
+        }
+        Ok(Watcher {
+            inotify: inotify,
+            dirs: watched,
+            pending: HashMap::new(),
+            idle: idle,
+        })
+    }
+
+    /// Records the caches the events read since the last call touched
+    fn read_events(&mut self, buffer: &mut [u8]) -> Result<(), WatchError> {
+        loop {
+            let events = match self.inotify.read_events(buffer) {
+                Ok(v) => v,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(WatchError::Inotify(e)),
+            };
+            let now = Instant::now();
+            for event in events {
+                if event.mask.contains(EventMask::Q_OVERFLOW) {
+                    warn!("Missed file system events, some changed caches may not be merged");
+                    continue;
+                }
+                let (dir, name) = match (self.dirs.get(&event.wd), event.name) {
+                    (Some(dir), Some(name)) => (dir, name),
+                    _ => continue,
+                };
+                if Path::new(name).extension() != Some(OsStr::new(CACHE_EXTENSION)) {
+                    continue;
+                }
+                let path = dir.join(name);
+                if event.mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM) {
+                    self.pending.remove(&path);
+                } else {
+                    self.pending.insert(path, now);
+                }
+            }
+        }
+    }
+
+    /// Takes the caches that haven't changed for the idle period
+    fn take_idle(&mut self, now: Instant) -> Vec<PathBuf> {
+        let idle = self.idle;
+        let mut done: Vec<_> = self.pending.iter()
+            .filter(|&(_, &changed)| now.duration_since(changed) >= idle)
+            .map(|(path, _)| path.clone())
+            .collect();
+        done.sort();
+        for path in done.iter() {
+            self.pending.remove(path);
+        }
+        done
+    }
+
+    /// Merges finished caches into `sink` until an error stops watching. Caches that fail to
+    /// merge are reported and skipped.
+    pub fn run(&mut self, sink: &Sink, options: &ReadOptions) -> Result<(), WatchError> {
+        let mut buffer = [0u8; 4096];
+        loop {
+            self.read_events(&mut buffer)?;
+            for path in self.take_idle(Instant::now()) {
+                if sink.is_master(&path) {
+                    continue;
+                }
+                debug!("{} went idle, merging", path.display());
+                match sink.merge(&path, options) {
+                    Ok(summary) => info!(
+                        "Merged {} new entries from {}, now {} entries",
+                        summary.added,
+                        path.display(),
+                        summary.total
+                    ),
+                    Err(e) => warn!("Not merged: {}", e),
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "browse")]
+
+mod common;
+
+use dxvk_cache_tool::{
+    browse::{self, Browser, Mark, Outputs, Query, SortKey},
+    dxvk::{HashDisplay, ReadOptions},
+};
+use common::fixture;
+
+fn open(name: &str) -> Browser {
+    browse::open(&fixture(name), &ReadOptions::default()).unwrap()
+}
+
+fn selected_hash(b: &Browser) -> String {
+    HashDisplay::new(&b.entries()[b.selected().unwrap()].entry.hash).to_string()
+}
+
+#[test]
+fn queries() {
+    assert_eq!("vs|FS".parse(), Ok(Query::Stages(0x11)));
+    assert_eq!("cs".parse(), Ok(Query::Stages(0x20)));
+    assert!(matches!("d877".parse(), Ok(Query::Hex(_, bytes)) if bytes == [0xd8, 0x77]));
+    assert!(matches!("d87".parse(), Ok(Query::Hex(_, bytes)) if bytes.is_empty()));
+    assert!("".parse::<Query>().is_err());
+    assert!("VS|XS".parse::<Query>().is_err());
+}
+
+#[test]
+fn sort_filter_and_search() {
+    let mut b = open("v10-extra.dxvk-cache");
+    assert_eq!(b.view(), [0, 1, 2, 3, 4, 5]);
+
+    b.set_sort(SortKey::Size);
+    let sizes: Vec<_> = b.view().iter().map(|&i| b.entries()[i].entry.data.len()).collect();
+    assert!(sizes.windows(2).all(|w| w[0] >= w[1]));
+
+    b.set_sort(SortKey::Position);
+    b.move_selection(1);
+    b.set_filter(Some("VS|FS".parse().unwrap()));
+    assert_eq!(b.view(), [1, 4, 5]);
+    // The selected entry stays selected while it's still listed
+    assert_eq!(b.selected(), Some(1));
+
+    b.set_filter(None);
+    assert!(b.search(&"d877".parse().unwrap()));
+    assert_eq!(selected_hash(&b), "d87750f20da207e161e7cb3c2bb69a70789696c6");
+    assert!(!b.search(&"ffeeddccbbaa9988".parse().unwrap()));
+
+    // Searching for bytes of an entry's data, like the hash of a shader it uses, finds it too
+    let data = &b.entries()[2].entry.data[8..16];
+    let query = data.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    assert!(b.search(&query.parse().unwrap()));
+    assert_eq!(b.selected(), Some(2));
+
+    b.set_filter(Some("TES".parse().unwrap()));
+    assert!(b.view().is_empty());
+    assert_eq!(b.selected(), None);
+    b.move_selection(1);
+    assert!(!b.search(&"CS".parse().unwrap()));
+}
+
+#[test]
+fn marks() {
+    let mut b = open("v10-extra.dxvk-cache");
+    b.toggle_mark(Mark::Export);
+    b.move_selection(1);
+    b.toggle_mark(Mark::Export);
+    b.toggle_mark(Mark::Remove);
+    b.move_selection(1);
+    b.toggle_mark(Mark::Remove);
+    b.toggle_mark(Mark::Remove);
+    assert_eq!(b.mark(0), Some(Mark::Export));
+    assert_eq!(b.mark(1), Some(Mark::Remove));
+    assert_eq!(b.mark(2), None);
+    assert_eq!((b.marked(Mark::Export), b.marked(Mark::Remove)), (1, 1));
+
+    let exported = b.exported();
+    assert_eq!(exported.entries.len(), 1);
+    assert!(exported.iter().all(|e| e.hash == b.entries()[0].entry.hash));
+    let kept = b.kept();
+    assert_eq!(kept.entries.len(), 5);
+    assert!(kept.iter().all(|e| e.hash != b.entries()[1].entry.hash));
+    assert_eq!(kept.header.version, b.header().version);
+}
+
+#[test]
+fn invalid_entries() {
+    let mut b = open("hash-mismatch.dxvk-cache");
+    assert!(b.entries().iter().any(|e| !e.valid));
+    // Damaged entries are listed, but never written out
+    b.toggle_mark(Mark::Export);
+    assert!(b.exported().iter().all(|e| e.is_valid()));
+    assert!(b.kept().iter().all(|e| e.is_valid()));
+}
+
+#[test]
+fn existing_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let outputs = Outputs {
+        export: dir.path().join("export.dxvk-cache"),
+        output: dir.path().join("output.dxvk-cache"),
+        compress: None,
+    };
+    let mut b = open("v10-extra.dxvk-cache");
+    std::fs::write(&outputs.export, "").unwrap();
+    std::fs::write(&outputs.output, "").unwrap();
+    // Only the files that would be written count
+    assert!(outputs.existing(&b).is_empty());
+    b.toggle_mark(Mark::Remove);
+    assert_eq!(outputs.existing(&b), [outputs.output.as_path()]);
+    b.move_selection(1);
+    b.toggle_mark(Mark::Export);
+    assert_eq!(outputs.existing(&b), [outputs.export.as_path(), outputs.output.as_path()]);
+    std::fs::remove_file(&outputs.export).unwrap();
+    assert_eq!(outputs.existing(&b), [outputs.output.as_path()]);
+}
+
+#[test]
+fn failed_writes_keep_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let outputs = Outputs {
+        export: dir.path().join("export.dxvk-cache"),
+        output: dir.path().join("output.dxvk-cache"),
+        compress: None,
+    };
+    std::fs::write(&outputs.output, "output").unwrap();
+    let mut b = open("v10-extra.dxvk-cache");
+    for _ in 0..b.entries().len() {
+        b.toggle_mark(Mark::Remove);
+        b.move_selection(1);
+    }
+    // Removing every entry would leave an empty cache, so the output is left alone
+    assert!(outputs.write_marks(&b).is_err());
+    assert_eq!(std::fs::read(&outputs.output).unwrap(), b"output");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    b.toggle_mark(Mark::Remove);
+    assert_eq!(
+        outputs.write_marks(&b).unwrap(),
+        format!("wrote 1 entries to {}", outputs.output.display())
+    );
+    assert!(!outputs.export.exists());
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::path::Path;
+use dxvk_cache_tool::{
+    bundle::{self, BundleError, BundleReader, Metadata},
+    dxvk::{DxvkStateCache, ReadOptions},
+};
+use common::{fixture, tool};
+
+fn cache(name: &str) -> DxvkStateCache {
+    DxvkStateCache::from_file(fixture(name), &ReadOptions::default()).unwrap()
+}
+
+fn metadata(game: &str, contributor: &str) -> Metadata {
+    Metadata {
+        game: game.into(),
+        dxvk_version: Some("2.3".into()),
+        contributor: Some(contributor.into()),
+        created: 1_700_000_000,
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let caches = vec![
+        (metadata("one", "alice"), cache("v10.dxvk-cache")),
+        (metadata("two", "bob"), cache("v07.dxvk-cache")),
+    ];
+    let mut data = Vec::new();
+    bundle::write_bundle(&mut data, &caches).unwrap();
+
+    let mut reader = BundleReader::new(&data[..], &ReadOptions::default()).unwrap();
+    let index = reader.index().to_vec();
+    assert_eq!(index.len(), 2);
+    assert_eq!(index[1].metadata, caches[1].0);
+    assert_eq!(index[1].version.get(), 7);
+    assert_eq!(index[1].entries, 4);
+    for (expected, entry) in caches.iter().zip(index.iter()) {
+        let (read_entry, read) = reader.next_cache().unwrap().unwrap();
+        assert_eq!(&read_entry, entry);
+        assert_eq!(read.entries, expected.1.entries);
+    }
+    assert!(reader.next_cache().unwrap().is_none());
+}
+
+#[test]
+fn index_is_read_without_caches() {
+    let caches = vec![(metadata("one", "alice"), cache("v10.dxvk-cache"))];
+    let mut data = Vec::new();
+    bundle::write_bundle(&mut data, &caches).unwrap();
+    data.truncate(data.len() - 10);
+
+    let mut reader = BundleReader::new(&data[..], &ReadOptions::default()).unwrap();
+    assert_eq!(reader.index()[0].entries, 4);
+    assert!(matches!(reader.next_cache(), Err(BundleError::Read { .. })));
+}
+
+#[test]
+fn invalid_bundles_are_rejected() {
+    let options = ReadOptions::default();
+    assert!(matches!(BundleReader::new(&b"DXVKCACHE\0\0\0\0\0\0\0"[..], &options), Err(BundleError::MagicMismatch)));
+
+    let mut data = b"DXVKBNDL\x01\0\0\0".to_vec();
+    let index = br#"[{"game":"../escape","created":0,"version":10,"entry_size":0,"entries":0,"offset":0,"length":0}]"#;
+    data.extend_from_slice(&(index.len() as u32).to_le_bytes());
+    data.extend_from_slice(index);
+    assert!(matches!(BundleReader::new(&data[..], &options), Err(BundleError::InvalidGame(..))));
+
+    let caches = vec![(metadata("../escape", "alice"), cache("v10.dxvk-cache"))];
+    assert!(matches!(bundle::write_bundle(Vec::new(), &caches), Err(BundleError::InvalidGame(..))));
+}
+
+#[test]
+fn errors_name_the_bundle() {
+    let dir = tempfile::tempdir().unwrap();
+    let options = ReadOptions::default();
+    let path = dir.path().join("cut.bundle");
+    let caches = vec![(metadata("one", "alice"), cache("v10.dxvk-cache"))];
+    let mut data = Vec::new();
+    bundle::write_bundle(&mut data, &caches).unwrap();
+    data.truncate(data.len() - 10);
+    std::fs::write(&path, &data).unwrap();
+
+    let e = bundle::open(&path, &options).unwrap().next_cache().unwrap_err();
+    assert_eq!(e.path(), Some(path.as_path()));
+    assert!(matches!(e.inner(), BundleError::Read { .. }));
+    assert!(e.to_string().starts_with(&format!("{}: one: ", path.display())));
+
+    std::fs::write(&path, b"DXVKCACHE\0\0\0\0\0\0\0").unwrap();
+    let e = bundle::open(&path, &options).err().unwrap();
+    assert_eq!(e.path(), Some(path.as_path()));
+    assert!(matches!(e.inner(), BundleError::MagicMismatch));
+    // Wrapping again doesn't repeat the path
+    assert_eq!(e.in_file("other").to_string(), format!("{}: Not a cache bundle", path.display()));
+}
+
+#[test]
+fn timestamps() {
+    assert_eq!(bundle::format_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(bundle::format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(bundle::format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+}
+
+#[test]
+fn create_list_extract_merge() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_owned();
+    let v10 = fixture("v10.dxvk-cache");
+    let extra = fixture("v10-extra.dxvk-cache");
+    let v07 = fixture("v07.dxvk-cache");
+
+    let output = tool(&[
+        "bundle", "create", "-o", &path("a.bundle"), "--contributor", "alice", "--dxvk-version", "2.3",
+        &format!("game={}", v10.display()), v07.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let output = tool(&[
+        "--compress", "zstd", "bundle", "create", "-o", &path("b.bundle"), "--contributor", "bob",
+        &format!("game={}", extra.display()),
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let output = tool(&["bundle", "list", &path("a.bundle")]);
+    let listed = String::from_utf8(output.stdout).unwrap();
+    let fields: Vec<Vec<_>> = listed.lines().map(|l| l.split('\t').collect()).collect();
+    assert_eq!(fields.len(), 2);
+    assert_eq!(fields[0][..5], ["game", "v10", "4", "2.3", "alice"]);
+    assert_eq!(fields[1][..5], ["v07", "v7", "4", "2.3", "alice"]);
+
+    let output = tool(&["bundle", "merge", "-o", &path("merged.bundle"), &path("a.bundle"), &path("b.bundle")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let output = tool(&["bundle", "list", &path("merged.bundle")]);
+    let listed = String::from_utf8(output.stdout).unwrap();
+    let fields: Vec<Vec<_>> = listed.lines().map(|l| l.split('\t').collect()).collect();
+    assert_eq!(fields[0][..5], ["game", "v10", "6", "2.3", "alice, bob"]);
+
+    let output = tool(&["bundle", "extract", &path("merged.bundle"), "-d", &path("out"), "game"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let extracted = DxvkStateCache::from_file(path("out/game.dxvk-cache"), &ReadOptions::default()).unwrap();
+    assert_eq!(extracted.entries.len(), 6);
+    assert!(!Path::new(&path("out/v07.dxvk-cache")).exists());
+
+    let output = tool(&["bundle", "extract", &path("merged.bundle"), "-d", &path("out"), "missing"]);
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,451 @@
+//! Runs each subcommand against the fixture caches in `tests/fixtures`, comparing the output
+//! against the golden files in `tests/golden`. Run with `UPDATE_GOLDEN=1` to rewrite them after
+//! an intended change in output.
+
+mod common;
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::Path,
+    process::Output,
+};
+use tempfile::TempDir;
+use common::{fixture, FIXTURES};
+
+const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const TMP: &str = "$TMP/";
+
+const DAMAGED: [&str; 5] = [
+    "truncated.dxvk-cache",
+    "bad-magic.dxvk-cache",
+    "zero-version.dxvk-cache",
+    "byte-swapped.dxvk-cache",
+    "hash-mismatch.dxvk-cache",
+];
+
+/// Built field by field by `regenerate.sh` rather than by `generate`
+const HAND_BUILT: [&str; 2] = ["dxvk-v07.dxvk-cache", "dxvk-v15.dxvk-cache"];
+
+fn valid_fixtures() -> Vec<String> {
+    let mut fixtures: Vec<_> = (1..=17).map(|v| format!("v{:02}.dxvk-cache", v)).collect();
+    fixtures.extend(HAND_BUILT.iter().map(|&s| s.to_owned()));
+    fixtures
+}
+
+fn all_fixtures() -> Vec<String> {
+    let mut fixtures = valid_fixtures();
+    fixtures.push("v10-extra.dxvk-cache".into());
+    fixtures.extend(DAMAGED.iter().map(|&s| s.to_owned()));
+    fixtures
+}
+
+/// A series of invocations whose rendered output gets compared against a single golden file.
+/// Arguments starting with `$TMP/` refer to a scratch directory, everything else is relative to
+/// the fixture directory.
+struct Session {
+    tmp:    TempDir,
+    output: String,
+}
+
+impl Session {
+    fn new() -> Self {
+        Session {
+            tmp: tempfile::tempdir().unwrap(),
+            output: String::new(),
+        }
+    }
+
+    fn invoke(&self, args: &[&str]) -> Output {
+        let args: Vec<_> = args.iter()
+            .map(|arg| match arg.strip_prefix(TMP) {
+                Some(name) => self.tmp.path().join(name).into_os_string(),
+                None => arg.into(),
+            })
+            .collect();
+        common::command()
+            .args(&args)
+            .current_dir(FIXTURES)
+            .env("RUST_LOG", "warn")
+            .output()
+            .unwrap()
+    }
+
+    /// Runs the tool and records its exit status, stdout and logged warnings. Output is sorted
+    /// when `sorted` is set, for commands that don't define an entry order.
+    fn run(&mut self, args: &[&str], sorted: bool) -> Output {
+        let output = self.invoke(args);
+        let tmp = self.tmp.path().to_string_lossy().into_owned();
+        let scrub = |s: &[u8]| String::from_utf8_lossy(s).replace(&tmp, "$TMP");
+        let out = &mut self.output;
+        writeln!(out, "$ {}", args.join(" ")).unwrap();
+        writeln!(out, "status: {}", output.status.code().unwrap_or(-1)).unwrap();
+        let stdout = scrub(&output.stdout);
+        let mut lines: Vec<_> = stdout.lines().collect();
+        if sorted {
+            lines.sort_unstable();
+        }
+        for line in lines {
+            writeln!(out, "{}", line).unwrap();
+        }
+        // Keep only the level of the "[timestamp LEVEL target] " prefix env_logger adds
+        for line in scrub(&output.stderr).lines() {
+            match line.find("] ") {
+                Some(i) => {
+                    let level = line[..i].split_whitespace().nth(1).unwrap_or("");
+                    writeln!(out, "{}: {}", level.to_lowercase(), &line[i + 2..]).unwrap();
+                },
+                None => writeln!(out, "{}", line).unwrap(),
+            }
+        }
+        out.push('\n');
+        output
+    }
+
+    /// Records the contents of a file the tool wrote to the scratch directory
+    fn show(&mut self, name: &str) {
+        let tmp = self.tmp.path().to_string_lossy().into_owned();
+        let contents = fs::read_to_string(self.tmp.path().join(name)).unwrap();
+        writeln!(self.output, "> {}{}", TMP, name).unwrap();
+        self.output.push_str(&contents.replace(&tmp, "$TMP"));
+        self.output.push('\n');
+    }
+
+    fn check(self, name: &str) {
+        let path = Path::new(GOLDEN).join(name).with_extension("txt");
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(GOLDEN).unwrap();
+            fs::write(&path, &self.output).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1 to create it)", path.display(), e));
+        assert!(
+            self.output == expected,
+            "output differs from {}:\n{}",
+            path.display(),
+            self.output
+        );
+    }
+}
+
+#[test]
+fn inspect() {
+    let mut s = Session::new();
+    for f in all_fixtures() {
+        s.run(&["inspect", &f], false);
+    }
+    s.run(&["inspect", "v01.dxvk-cache", "v17.dxvk-cache"], false);
+    s.run(&["--assume-endian", "big", "inspect", "byte-swapped.dxvk-cache"], false);
+    // Same entries as v10.dxvk-cache, only stored big-endian
+    s.run(&["--assume-endian", "big", "list-entries", "byte-swapped.dxvk-cache"], true);
+    s.check("inspect");
+}
+
+#[test]
+fn list_entries() {
+    let mut s = Session::new();
+    for f in all_fixtures() {
+        s.run(&["list-entries", &f], true);
+    }
+    s.check("list-entries");
+}
+
+#[test]
+fn jumble() {
+    let mut s = Session::new();
+    for f in valid_fixtures() {
+        let out = format!("{}{}", TMP, f);
+        s.run(&["jumble", &f, &out], false);
+        let original = s.invoke(&["list-entries", &f]);
+        let jumbled = s.invoke(&["list-entries", &out]);
+        let sorted = |o: &Output| {
+            let mut lines: Vec<_> = String::from_utf8_lossy(&o.stdout).lines().map(String::from).collect();
+            lines.sort_unstable();
+            lines
+        };
+        assert_eq!(sorted(&original), sorted(&jumbled), "{} changed when jumbled", f);
+    }
+    for f in DAMAGED.iter() {
+        s.run(&["jumble", f, "$TMP/damaged.dxvk-cache"], false);
+    }
+    s.check("jumble");
+}
+
+#[test]
+fn merge() {
+    let mut s = Session::new();
+    for cmd in ["merge", "merge2"].iter() {
+        s.run(&[cmd, "-o", "$TMP/out.dxvk-cache", "v10.dxvk-cache", "v10-extra.dxvk-cache"], false);
+        s.run(&["list-entries", "$TMP/out.dxvk-cache"], true);
+        s.run(&[cmd, "-o", "$TMP/legacy.dxvk-cache", "v07.dxvk-cache", "v07.dxvk-cache"], false);
+        s.run(&["list-entries", "$TMP/legacy.dxvk-cache"], true);
+        s.run(&[cmd, "-o", "$TMP/mismatch.dxvk-cache", "v10.dxvk-cache", "v11.dxvk-cache"], false);
+        s.run(&[cmd, "-o", "$TMP/truncated.dxvk-cache", "truncated.dxvk-cache", "v10.dxvk-cache"], false);
+        s.run(&[cmd, "-o", "$TMP/invalid.dxvk-cache", "hash-mismatch.dxvk-cache", "v10.dxvk-cache"], false);
+        s.run(&[cmd, "-o", "$TMP/bad.dxvk-cache", "v10.dxvk-cache", "bad-magic.dxvk-cache"], false);
+
+        fs::copy(fixture("v10.dxvk-cache"), s.tmp.path().join("append.dxvk-cache")).unwrap();
+        s.run(&[cmd, "--append", "-o", "$TMP/append.dxvk-cache", "v10-extra.dxvk-cache"], false);
+        s.run(&["list-entries", "$TMP/append.dxvk-cache"], true);
+        let appended = fs::read(s.tmp.path().join("append.dxvk-cache")).unwrap();
+        let original = fs::read(fixture("v10.dxvk-cache")).unwrap();
+        assert!(appended.starts_with(&original), "{} --append modified existing bytes", cmd);
+        s.run(&[cmd, "--append", "-o", "$TMP/append.dxvk-cache", "v11.dxvk-cache"], false);
+        fs::copy(fixture("truncated.dxvk-cache"), s.tmp.path().join("truncated.dxvk-cache")).unwrap();
+        s.run(&[cmd, "--append", "-o", "$TMP/truncated.dxvk-cache", "v10.dxvk-cache"], false);
+        fs::copy(fixture("byte-swapped.dxvk-cache"), s.tmp.path().join("big.dxvk-cache")).unwrap();
+        s.run(&["--assume-endian", "big", cmd, "--append", "-o", "$TMP/big.dxvk-cache", "byte-swapped.dxvk-cache"], false);
+        let unchanged = fs::read(s.tmp.path().join("big.dxvk-cache")).unwrap();
+        assert!(unchanged == fs::read(fixture("byte-swapped.dxvk-cache")).unwrap());
+    }
+    s.check("merge");
+}
+
+#[test]
+fn merge_report() {
+    let mut s = Session::new();
+    let inputs = ["v10.dxvk-cache", "v10-extra.dxvk-cache", "hash-mismatch.dxvk-cache"];
+    s.run(&[&["merge", "--dry-run", "--report", "$TMP/report.txt"][..], &inputs].concat(), false);
+    s.show("report.txt");
+    // Reversing the inputs changes which entries count as new, but not the report
+    let reversed: Vec<_> = inputs.iter().rev().cloned().collect();
+    s.run(&[&["merge", "-o", "$TMP/out.dxvk-cache", "--report", "$TMP/report.json"][..], &reversed].concat(), false);
+    s.show("report.json");
+    s.check("merge-report");
+}
+
+#[test]
+fn keep_going() {
+    let mut s = Session::new();
+    let inputs = ["v10.dxvk-cache", "bad-magic.dxvk-cache", "zero-version.dxvk-cache", "truncated.dxvk-cache", "v10-extra.dxvk-cache"];
+    for cmd in ["merge", "merge2"].iter() {
+        s.run(&[&[cmd, "--keep-going", "-o", "$TMP/out.dxvk-cache"][..], &inputs].concat(), false);
+        s.run(&["list-entries", "$TMP/out.dxvk-cache"], true);
+        s.run(&[cmd, "--keep-going", "-o", "$TMP/none.dxvk-cache", "bad-magic.dxvk-cache", "missing.dxvk-cache"], false);
+        s.run(&[cmd, "--keep-going", "-o", "$TMP/good.dxvk-cache", "v10.dxvk-cache"], false);
+    }
+    s.run(&[&["list-entries", "--keep-going"][..], &inputs].concat(), true);
+    s.run(&[&["list-entries"][..], &inputs].concat(), true);
+
+    // Inputs that can be read but not merged are skipped too
+    fs::copy(fixture("v10-extra.dxvk-cache"), s.tmp.path().join("extra.dxvk-cache")).unwrap();
+    fs::write(s.tmp.path().join("extra.dxvk-cache.provenance"), "not a provenance record\n").unwrap();
+    let inputs = ["v10.dxvk-cache", "v11.dxvk-cache", "$TMP/extra.dxvk-cache"];
+    s.run(&[&["merge2", "--keep-going", "-o", "$TMP/out.dxvk-cache"][..], &inputs].concat(), false);
+    let merge = ["merge", "--keep-going", "--provenance", "--report", "$TMP/report.txt", "-o", "$TMP/out.dxvk-cache"];
+    s.run(&[&merge[..], &inputs].concat(), false);
+    s.show("report.txt");
+    s.run(&["list-entries", "$TMP/out.dxvk-cache"], true);
+    s.check("keep-going");
+}
+
+#[test]
+fn error_format() {
+    let mut s = Session::new();
+    let json = ["--error-format", "json"];
+    let run = |s: &mut Session, args: &[&str]| {
+        s.run(&[&json[..], args].concat(), false);
+    };
+    run(&mut s, &["inspect", "bad-magic.dxvk-cache"]);
+    run(&mut s, &["inspect", "missing.dxvk-cache"]);
+    run(&mut s, &["merge", "-o", "$TMP/out.dxvk-cache", "v10.dxvk-cache", "v11.dxvk-cache"]);
+    run(&mut s, &["merge", "--keep-going", "-o", "$TMP/out.dxvk-cache", "v10.dxvk-cache", "zero-version.dxvk-cache"]);
+    run(&mut s, &["merge", "--keep-going", "-o", "$TMP/out.dxvk-cache", "zero-version.dxvk-cache"]);
+    // Each skipped input is listed with its own kind
+    let inputs = ["v10.dxvk-cache", "v11.dxvk-cache", "bad-magic.dxvk-cache", "missing.dxvk-cache"];
+    run(&mut s, &[&["merge2", "--keep-going", "-o", "$TMP/out.dxvk-cache"][..], &inputs].concat());
+    run(&mut s, &["store", "--store", "$TMP/store", "add", "--game", "game", "truncated.dxvk-cache"]);
+    run(&mut s, &["merge", "--target-version", "newest", "v10.dxvk-cache"]);
+    run(&mut s, &["bundle", "list", "v10.dxvk-cache"]);
+    run(&mut s, &["show", "v10-extra.dxvk-cache", "d"]);
+    run(&mut s, &["show", "v10.dxvk-cache", "0000"]);
+    run(&mut s, &["where", "/"]);
+    fs::write(s.tmp.path().join("empty.dxvk-cache"), b"DXVK\x0a\0\0\0\0\0\0\0").unwrap();
+    run(&mut s, &["jumble", "$TMP/empty.dxvk-cache", "$TMP/jumbled.dxvk-cache"]);
+    assert!(!s.tmp.path().join("jumbled.dxvk-cache").exists());
+    run(&mut s, &["serve", "--store", "$TMP/store", "--listen", "not-an-address"]);
+    // Nothing listens on port 1
+    run(&mut s, &["pull", "http://127.0.0.1:1", "game", "-o", "$TMP/pulled.dxvk-cache"]);
+    s.run(&["merge", "--error-format=json", "--no-such-flag"], false);
+    s.run(&["--error-format", "yaml", "inspect", "v10.dxvk-cache"], false);
+    s.check("error-format");
+}
+
+#[test]
+fn target_version() {
+    let mut s = Session::new();
+    let inputs = ["v10.dxvk-cache", "v11.dxvk-cache", "v10-extra.dxvk-cache", "v07.dxvk-cache"];
+    for target in ["first", "latest", "v7", "12"].iter() {
+        s.run(&[&["merge", "--target-version", target, "-o", "$TMP/out.dxvk-cache"][..], &inputs].concat(), false);
+        s.run(&["inspect", "$TMP/out.dxvk-cache"], false);
+    }
+    s.run(&[&["merge", "--target-version", "first", "--dry-run", "--report", "$TMP/report.txt"][..], &inputs].concat(), false);
+    s.show("report.txt");
+    s.run(&["merge", "--target-version", "newest", "v10.dxvk-cache"], false);
+    // Inputs are read in full before picking the latest version, with the same handling of
+    // unreadable ones as the merge itself
+    let v17 = fs::read(fixture("v17.dxvk-cache")).unwrap();
+    fs::write(s.tmp.path().join("truncated-v17.dxvk-cache"), &v17[..100]).unwrap();
+    let inputs = ["v10.dxvk-cache", "v10-extra.dxvk-cache", "$TMP/truncated-v17.dxvk-cache"];
+    s.run(&[&["merge", "--target-version", "latest", "-o", "$TMP/out.dxvk-cache"][..], &inputs].concat(), false);
+    s.run(&[&["merge", "--target-version", "latest", "--keep-going", "-o", "$TMP/out.dxvk-cache"][..], &inputs].concat(), false);
+    s.run(&["inspect", "$TMP/out.dxvk-cache"], false);
+    s.check("target-version");
+}
+
+#[test]
+fn limits() {
+    let mut s = Session::new();
+    // Bare headers, around the bounds on the fixed entry size of legacy caches
+    let header = |version: u32, entry_size: u32| {
+        [&b"DXVK"[..], &version.to_le_bytes(), &entry_size.to_le_bytes()].concat()
+    };
+    for (version, entry_size) in [(7, 20), (7, 21), (7, 65536), (7, 65537), (1, 0), (10, 65537)].iter() {
+        let name = format!("v{}-size-{}.dxvk-cache", version, entry_size);
+        fs::write(s.tmp.path().join(&name), header(*version, *entry_size)).unwrap();
+        s.run(&["inspect", &format!("{}{}", TMP, name)], false);
+    }
+    // The entries of v10.dxvk-cache hold 53, 82, 49 and 53 bytes
+    s.run(&["--max-entry-size", "40", "list-entries", "v10.dxvk-cache"], true);
+    s.run(&["--max-entry-size", "82", "list-entries", "v10.dxvk-cache"], true);
+    s.run(&["--max-cache-size", "100", "list-entries", "v10.dxvk-cache"], true);
+    s.run(&["--max-cache-size", "1k", "list-entries", "v10.dxvk-cache"], true);
+    s.run(&["--max-entry-size", "40", "merge", "-o", "$TMP/out.dxvk-cache", "v10.dxvk-cache"], false);
+    s.run(&["--max-entry-size", "1x", "list-entries", "v10.dxvk-cache"], false);
+    s.run(&["--max-cache-size", "99999999999G", "list-entries", "v10.dxvk-cache"], false);
+    s.check("limits");
+}
+
+#[test]
+fn similarity() {
+    let mut s = Session::new();
+    let files = ["v10.dxvk-cache", "v10-extra.dxvk-cache", "v11.dxvk-cache", "v07.dxvk-cache"];
+    s.run(&[&["similarity"][..], &files].concat(), false);
+    s.run(&[&["similarity", "--containment"][..], &files].concat(), false);
+    s.run(&[&["similarity", "--cluster", "0.5"][..], &files].concat(), false);
+    s.run(&[&["similarity", "--cluster", "0"][..], &files].concat(), false);
+    s.run(&["similarity", "--cluster", "1.5", "v10.dxvk-cache"], false);
+    s.check("similarity");
+}
+
+#[test]
+fn show() {
+    let mut s = Session::new();
+    s.run(&["show", "v10.dxvk-cache", "4748aec54dbbf7f1c14cafb03b251a3e7f7ca36f"], false);
+    s.run(&["show", "v10.dxvk-cache", "DAD4"], false);
+    s.run(&["show", "v05.dxvk-cache", "fee8"], false);
+    s.run(&["show", "v10-extra.dxvk-cache", "d"], false);
+    s.run(&["show", "v10.dxvk-cache", "0000"], false);
+    s.run(&["show", "v10.dxvk-cache", "xyz"], false);
+    s.run(&["show", "hash-mismatch.dxvk-cache", "4748"], false);
+    s.check("show");
+}
+
+#[test]
+fn difference() {
+    let mut s = Session::new();
+    s.run(&["difference", "v10-extra.dxvk-cache", "v10.dxvk-cache"], true);
+    s.run(&["difference", "v10.dxvk-cache", "v10-extra.dxvk-cache"], true);
+    s.run(&["difference", "-o", "$TMP/diff.dxvk-cache", "v10-extra.dxvk-cache", "v10.dxvk-cache"], false);
+    s.run(&["inspect", "$TMP/diff.dxvk-cache"], false);
+    s.run(&["difference", "v10.dxvk-cache", "v11.dxvk-cache"], false);
+    s.run(&["difference", "v10.dxvk-cache", "zero-version.dxvk-cache"], false);
+    s.check("difference");
+}
+
+/// The fixtures were produced by `generate`, so it has to reproduce them byte for byte
+#[test]
+fn generate() {
+    let s = Session::new();
+    for v in 1..=17 {
+        let name = format!("v{:02}.dxvk-cache", v);
+        let out = format!("{}{}", TMP, name);
+        let (version, seed) = (v.to_string(), v.to_string());
+        let size = if v <= 7 { "64" } else { "32-96" };
+        let output = s.invoke(&[
+            "generate", "--cache-version", &version, "-n", "4", "--size", size, "--seed", &seed, "-o", &out,
+        ]);
+        assert!(output.status.success(), "generating {} failed", name);
+        let generated = fs::read(s.tmp.path().join(&name)).unwrap();
+        let fixture = fs::read(fixture(&name)).unwrap();
+        assert!(generated == fixture, "generated {} differs from the fixture", name);
+    }
+}
+
+/// Writing the hand-built fixtures back out has to reproduce them byte for byte
+#[test]
+fn hand_built() {
+    let s = Session::new();
+    for name in HAND_BUILT.iter() {
+        let out = format!("{}{}", TMP, name);
+        assert!(s.invoke(&["merge", "-o", &out, name]).status.success(), "merging {} failed", name);
+        let written = fs::read(s.tmp.path().join(name)).unwrap();
+        let fixture = fs::read(fixture(name)).unwrap();
+        assert!(written == fixture, "writing {} changed it", name);
+    }
+}
+
+#[test]
+fn store() {
+    let mut s = Session::new();
+    let store = ["store", "--store", "$TMP/store"];
+    let run = |s: &mut Session, args: &[&str], sorted: bool| {
+        s.run(&[&store[..], args].concat(), sorted);
+    };
+    run(&mut s, &["add", "--game", "game", "v10.dxvk-cache"], false);
+    run(&mut s, &["add", "--game", "game", "v10-extra.dxvk-cache", "v10.dxvk-cache"], false);
+    run(&mut s, &["add", "--game", "game", "v11.dxvk-cache"], false);
+    run(&mut s, &["add", "v07.dxvk-cache", "hash-mismatch.dxvk-cache"], false);
+    run(&mut s, &["add", "--game", "../escape", "v10.dxvk-cache"], false);
+    run(&mut s, &["list"], false);
+    run(&mut s, &["get", "game", "-o", "$TMP/game.dxvk-cache"], false);
+    s.run(&["list-entries", "$TMP/game.dxvk-cache"], true);
+    run(&mut s, &["rm", "v07"], false);
+    run(&mut s, &["list"], false);
+    run(&mut s, &["rm", "v07"], false);
+    run(&mut s, &["get", "v07", "-o", "$TMP/v07.dxvk-cache"], false);
+
+    // Entries shared between games are only stored once, while those of removed games stay
+    // until gc runs
+    run(&mut s, &["add", "--game", "other", "v10.dxvk-cache"], false);
+    let objects = |s: &Session| {
+        fs::read_dir(s.tmp.path().join("store/objects"))
+            .unwrap()
+            .map(|dir| fs::read_dir(dir.unwrap().path()).unwrap().count())
+            .sum::<usize>()
+    };
+    assert_eq!(objects(&s), 6 + 4);
+    run(&mut s, &["gc"], false);
+    assert_eq!(objects(&s), 6);
+    run(&mut s, &["rm", "game"], false);
+    run(&mut s, &["gc"], false);
+    assert_eq!(objects(&s), 4);
+    run(&mut s, &["get", "other", "-o", "$TMP/other.dxvk-cache"], false);
+    s.run(&["list-entries", "$TMP/other.dxvk-cache"], true);
+
+    s.check("store");
+}
+
+#[test]
+fn compressed() {
+    let mut s = Session::new();
+    for (format, magic) in [("zstd", &b"\x28\xb5\x2f\xfd"[..]), ("xz", b"\xfd7zXZ\0"), ("gzip", b"\x1f\x8b")].iter() {
+        // Named like a plain cache, so only the contents tell the format
+        let out = format!("{}{}.dxvk-cache", TMP, format);
+        s.run(&["--compress", format, "jumble", "v10.dxvk-cache", &out], false);
+        let written = fs::read(s.tmp.path().join(format!("{}.dxvk-cache", format))).unwrap();
+        assert!(written.starts_with(magic), "{} output lacks its magic bytes", format);
+        s.run(&["inspect", &out], false);
+        s.run(&["list-entries", &out], true);
+    }
+    s.run(&["merge", "-o", "$TMP/merged.dxvk-cache", "$TMP/zstd.dxvk-cache", "$TMP/xz.dxvk-cache", "v10-extra.dxvk-cache"], false);
+    s.run(&["inspect", "$TMP/merged.dxvk-cache"], false);
+    s.run(&["--compress", "xz", "merge2", "-o", "$TMP/merged.xz", "$TMP/gzip.dxvk-cache", "v10-extra.dxvk-cache"], false);
+    s.run(&["difference", "$TMP/merged.xz", "$TMP/zstd.dxvk-cache"], true);
+    s.run(&["merge", "--append", "-o", "$TMP/zstd.dxvk-cache", "v10-extra.dxvk-cache"], false);
+    s.run(&["--compress", "gzip", "merge", "--append", "-o", "$TMP/plain.dxvk-cache", "v10.dxvk-cache"], false);
+    s.run(&["--compress", "lz4", "jumble", "v10.dxvk-cache", "$TMP/lz4.dxvk-cache"], false);
+    s.check("compressed");
+}
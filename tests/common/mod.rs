@@ -0,0 +1,31 @@
+//! Helpers shared by the integration tests. Each test crate only uses some of them.
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+pub const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(FIXTURES).join(name)
+}
+
+/// The tool's binary, only logging errors
+pub fn command() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_dxvk-cache-tool"));
+    command.env("RUST_LOG", "error");
+    command
+}
+
+pub fn tool(args: &[&str]) -> Output {
+    command().args(args).output().unwrap()
+}
+
+/// Writes `contents` to `path`, creating its parent directories
+pub fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
@@ -0,0 +1,84 @@
+#!/bin/sh
+# Regenerates the fixture caches. The entries are synthetic, produced by the
+# `generate` subcommand with fixed seeds, so the files are reproducible and
+# don't contain any game's pipelines.
+#
+# There is no cache written by DXVK itself among them. DXVK only writes one
+# while a game runs on a Vulkan GPU, and the result holds that game's shaders
+# and pipeline state, which can't be redistributed here. The generator follows
+# the entry layout of dxvk_state_cache_types.h instead. dxvk-v07 and dxvk-v15
+# at the end are built field by field from that layout without the tool, so
+# reading them checks the parser against something other than its own writer.
+set -e
+cd "$(dirname "$0")"
+tool() {
+    cargo run --quiet -- "$@"
+}
+
+for v in $(seq 1 17); do
+    name=$(printf 'v%02d.dxvk-cache' "$v")
+    if [ "$v" -le 7 ]; then
+        tool generate --cache-version "$v" -n 4 --size 64 --seed "$v" -o "$name"
+    else
+        tool generate --cache-version "$v" -n 4 --size 32-96 --seed "$v" -o "$name"
+    fi
+done
+# Shares its first four entries with v10.dxvk-cache
+tool generate --cache-version 10 -n 6 --size 32-96 --seed 10 -o v10-extra.dxvk-cache
+
+# Damaged variants, all derived from v10.dxvk-cache
+head -c 150 v10.dxvk-cache > truncated.dxvk-cache
+{ printf 'DXVC'; tail -c +5 v10.dxvk-cache; } > bad-magic.dxvk-cache
+{ printf 'DXVK\0\0\0\0'; tail -c +9 v10.dxvk-cache; } > zero-version.dxvk-cache
+# Big-endian throughout, the way a tool using the host's byte order would write
+# it on a big-endian machine: the header fields and each entry's 24-bit size
+python3 - v10.dxvk-cache byte-swapped.dxvk-cache <<'EOF'
+import struct, sys
+data = open(sys.argv[1], 'rb').read()
+out = bytearray(data[:4]) + struct.pack('>II', *struct.unpack('<II', data[4:12]))
+pos = 12
+while pos < len(data):
+    size = int.from_bytes(data[pos + 1:pos + 4], 'little')
+    out += data[pos:pos + 1] + size.to_bytes(3, 'big') + data[pos + 4:pos + 24 + size]
+    pos += 24 + size
+open(sys.argv[2], 'wb').write(out)
+EOF
+{ head -c 40 v10.dxvk-cache; printf '\377'; tail -c +42 v10.dxvk-cache; } > hash-mismatch.dxvk-cache
+
+# A legacy and a standard cache, with each field written out by hand. Legacy
+# entries are a fixed-size struct: the key of each of the six shader stages
+# (its stage flag and SHA-1, zero for unused stages), the pipeline state, and
+# the SHA-1 of all that followed by the SHA-1 of nothing. Standard entries
+# start with their stage mask and 24-bit size, then their SHA-1, then the keys
+# of the shaders in the mask followed by the pipeline state. The size of the
+# legacy pipeline state changed between DXVK releases and isn't taken from a
+# particular one.
+python3 - <<'EOF'
+import hashlib, struct
+
+def sha1(data):
+    return hashlib.sha1(data).digest()
+
+STAGES = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20]
+LEGACY_STATE_SIZE = 1824
+LEGACY_ENTRY_SIZE = len(STAGES) * 24 + LEGACY_STATE_SIZE + 20
+
+legacy = bytearray(b'DXVK' + struct.pack('<II', 7, LEGACY_ENTRY_SIZE))
+for i in range(3):
+    data = b''
+    for stage in STAGES:
+        if stage in (0x01, 0x10):
+            data += struct.pack('<I', stage) + sha1(b'legacy-%d-%d' % (i, stage))
+        else:
+            data += bytes(24)
+    data += bytes([i + 1]) * LEGACY_STATE_SIZE
+    legacy += data + sha1(data + sha1(b''))
+open('dxvk-v07.dxvk-cache', 'wb').write(legacy)
+
+standard = bytearray(b'DXVK' + struct.pack('<II', 15, 0))
+for i, (mask, state_size) in enumerate([(0x11, 312), (0x01, 96), (0x20, 40)]):
+    data = b''.join(sha1(b'standard-%d-%d' % (i, s)) for s in STAGES if mask & s)
+    data += bytes([i + 1]) * state_size
+    standard += bytes([mask]) + len(data).to_bytes(3, 'little') + sha1(data) + data
+open('dxvk-v15.dxvk-cache', 'wb').write(standard)
+EOF
@@ -0,0 +1,131 @@
+mod common;
+
+use std::{
+    io::Write,
+    net::{TcpListener, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+use dxvk_cache_tool::{
+    client::{Client, ClientError},
+    dxvk::ReadOptions,
+    server::{self, Server},
+    store::Store,
+};
+use tempfile::TempDir;
+use common::{fixture, tool};
+
+/// Serves a store in a fresh directory on a free localhost port, returning its URL
+fn serve() -> (TempDir, String) {
+    serve_with(server::MAX_UPLOAD_SIZE)
+}
+
+fn serve_with(max_upload_size: u64) -> (TempDir, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let options = ReadOptions::default();
+    let store = Store::open(dir.path().join("store"), options).unwrap();
+    let server = Server::bind_with_upload_limit("127.0.0.1:0", store, options, max_upload_size).unwrap();
+    let url = format!("http://{}", server.local_addr().unwrap());
+    thread::spawn(move || server.run());
+    (dir, url)
+}
+
+fn status(result: Result<impl Sized, ClientError>) -> u16 {
+    match result {
+        Err(ClientError::Status { status, .. }) => status,
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(..) => 200,
+    }
+}
+
+#[test]
+fn push_and_pull() {
+    let (dir, url) = serve();
+    let output = tool(&["push", &url, "--game", "game", fixture("v10.dxvk-cache").to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let output = tool(&["push", &url, "--game", "game", fixture("v10-extra.dxvk-cache").to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let output = tool(&["pull", &url, "--list"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "game\tv10\t6\n");
+
+    let pulled = dir.path().join("pulled.dxvk-cache");
+    let output = tool(&["pull", &url, "game", "-o", pulled.to_str().unwrap()]);
+    assert!(output.status.success());
+    let listed = tool(&["list-entries", pulled.to_str().unwrap()]);
+    assert_eq!(String::from_utf8(listed.stdout).unwrap().lines().count(), 6);
+
+    let output = tool(&["pull", &url, "missing", "-o", dir.path().join("missing").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("404"));
+}
+
+#[test]
+fn uploads_are_validated() {
+    let (_dir, url) = serve();
+    let client = Client::new(&url, ReadOptions::default());
+    assert_eq!(status(client.push("game", &fixture("v10.dxvk-cache"))), 200);
+    assert_eq!(status(client.push("game", &fixture("hash-mismatch.dxvk-cache"))), 400);
+    assert_eq!(status(client.push("game", &fixture("bad-magic.dxvk-cache"))), 400);
+    assert_eq!(status(client.push("game", &fixture("v11.dxvk-cache"))), 409);
+    assert_eq!(status(client.push("../escape", &fixture("v10.dxvk-cache"))), 400);
+    // Rejected uploads leave the stored cache alone
+    let games = client.list().unwrap();
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].entries, 4);
+    assert_eq!(client.pull("game").unwrap().entries.len(), 4);
+}
+
+#[test]
+fn game_names_are_escaped() {
+    let (_dir, url) = serve();
+    let client = Client::new(&format!("{}/", url), ReadOptions::default());
+    let game = "Half-Life 2: Episode Two";
+    client.push(game, &fixture("v10.dxvk-cache")).unwrap();
+    assert_eq!(client.list().unwrap()[0].game, game);
+    assert_eq!(client.pull(game).unwrap().entries.len(), 4);
+}
+
+#[test]
+fn uploads_are_limited() {
+    let (_dir, url) = serve_with(1000);
+    let client = Client::new(&url, ReadOptions::default());
+    assert_eq!(status(client.push("game", &fixture("dxvk-v07.dxvk-cache"))), 413);
+
+    // Compressed to fit the limit, but its entries still don't
+    let dir = tempfile::tempdir().unwrap();
+    let compressed = dir.path().join("dxvk-v07.dxvk-cache.xz");
+    let output = tool(&[
+        "--compress", "xz", "merge", "-o", compressed.to_str().unwrap(),
+        fixture("dxvk-v07.dxvk-cache").to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    assert!(std::fs::metadata(&compressed).unwrap().len() <= 1000);
+    assert_eq!(status(client.push("game", &compressed)), 400);
+    assert!(client.list().unwrap().is_empty());
+
+    assert_eq!(status(client.push("game", &fixture("v10.dxvk-cache"))), 200);
+}
+
+#[test]
+fn stalled_requests_dont_block_others() {
+    let (_dir, url) = serve();
+    // Announces a body and never sends it
+    let mut stalled = TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+    stalled.write_all(b"PUT /games/game HTTP/1.1\r\nHost: hub\r\nContent-Length: 100000\r\n\r\n").unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let client = Client::with_timeouts(&url, ReadOptions::default(), Duration::from_secs(5), Duration::from_secs(5));
+    assert_eq!(status(client.push("game", &fixture("v10.dxvk-cache"))), 200);
+    assert_eq!(client.list().unwrap().len(), 1);
+}
+
+#[test]
+fn client_gives_up_on_stalled_servers() {
+    // Connections get accepted by the OS but never answered
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let client = Client::with_timeouts(&url, ReadOptions::default(), Duration::from_secs(5), Duration::from_millis(200));
+    let start = Instant::now();
+    assert!(matches!(client.list(), Err(ClientError::Transport(..))));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,265 @@
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use dxvk_cache_tool::locate::{
+    bottles,
+    heroic,
+    lutris,
+    steam,
+    vdf,
+    FoundCache,
+    Launcher,
+};
+use common::write;
+
+fn manifest(appid: &str, name: &str) -> String {
+    format!(
+        "\"AppState\"\n{{\n\t\"appid\"\t\t\"{}\"\n\t\"name\"\t\t\"{}\"\n\t\"installdir\"\t\t\"{}\"\n}}\n",
+        appid, name, name
+    )
+}
+
+fn steam_cache(appid: &str, name: Option<&str>, path: PathBuf) -> FoundCache {
+    FoundCache {
+        launcher: Launcher::Steam,
+        game_id: appid.into(),
+        game_name: name.map(From::from),
+        path: path,
+    }
+}
+
+/// A Steam root with a second library folder, holding caches in `shadercache` and inside a
+/// Proton prefix
+fn fake_steam(dir: &Path) -> (PathBuf, PathBuf) {
+    let root = dir.join("Steam");
+    let library = dir.join("Library");
+    write(
+        &root.join("steamapps/libraryfolders.vdf"),
+        &format!(
+            "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t\t\"apps\" {{ \"1091500\" \"0\" }}\n\t}}\n}}\n",
+            root.display(),
+            library.display()
+        ),
+    );
+    write(&root.join("steamapps/appmanifest_2050650.acf"), &manifest("2050650", "Resident Evil 4"));
+    write(&root.join("steamapps/shadercache/2050650/DXVK_state_cache/re4.dxvk-cache"), "");
+    write(&root.join("steamapps/shadercache/2050650/DXVK_state_cache/steam.foz"), "");
+    // Uninstalled, so there's no manifest to name it
+    write(&root.join("steamapps/shadercache/570/DXVK_state_cache/dota2.dxvk-cache"), "");
+
+    write(&library.join("steamapps/appmanifest_1091500.acf"), &manifest("1091500", "Cyberpunk 2077"));
+    write(&library.join("steamapps/appmanifest_1091501.acf"), "\"AppState\"\n{\n\t\"appid\"");
+    let pfx = library.join("steamapps/compatdata/1091500/pfx");
+    write(&pfx.join("drive_c/Games/Cyberpunk2077.dxvk-cache"), "");
+    fs::create_dir_all(pfx.join("dosdevices")).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("/", pfx.join("dosdevices/z:")).unwrap();
+    (root, library)
+}
+
+#[test]
+fn finds_steam_caches() {
+    let dir = tempfile::tempdir().unwrap();
+    let (root, library) = fake_steam(dir.path());
+    let found = steam::find_caches(&root).unwrap();
+    let expected = vec![
+        steam_cache(
+            "1091500",
+            Some("Cyberpunk 2077"),
+            library.join("steamapps/compatdata/1091500/pfx/drive_c/Games/Cyberpunk2077.dxvk-cache"),
+        ),
+        steam_cache(
+            "2050650",
+            Some("Resident Evil 4"),
+            root.join("steamapps/shadercache/2050650/DXVK_state_cache/re4.dxvk-cache"),
+        ),
+        steam_cache("570", None, root.join("steamapps/shadercache/570/DXVK_state_cache/dota2.dxvk-cache")),
+    ];
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn legacy_library_folders() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("Steam");
+    let library = dir.path().join("Library");
+    fs::create_dir_all(&library).unwrap();
+    write(
+        &root.join("steamapps/libraryfolders.vdf"),
+        &format!(
+            "\"LibraryFolders\"\n{{\n\t\"TimeNextStatsReport\"\t\t\"1650000000\"\n\t\"1\"\t\t\"{}\"\n}}\n",
+            library.display()
+        ),
+    );
+    assert_eq!(steam::library_folders(&root).unwrap(), vec![root.clone(), library]);
+
+    write(&root.join("steamapps/libraryfolders.vdf"), "\"libraryfolders\"\n{\n");
+    assert_eq!(steam::library_folders(&root).unwrap(), vec![root]);
+}
+
+#[test]
+fn locate_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let (root, _) = fake_steam(dir.path());
+    let output = common::command()
+        .arg("locate")
+        .arg("--steam-root")
+        .arg(&root)
+        .env("HOME", dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[1],
+        format!(
+            "steam\t2050650\tResident Evil 4\t{}",
+            root.join("steamapps/shadercache/2050650/DXVK_state_cache/re4.dxvk-cache").display()
+        )
+    );
+    assert!(lines[2].starts_with("steam\t570\t-\t"));
+}
+
+fn cache(launcher: Launcher, id: &str, name: Option<&str>, path: PathBuf) -> FoundCache {
+    FoundCache {
+        launcher: launcher,
+        game_id: id.into(),
+        game_name: name.map(From::from),
+        path: path,
+    }
+}
+
+#[test]
+fn finds_lutris_caches() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("lutris/games");
+    let prefix = dir.path().join("Games/witcher3");
+    let overridden = dir.path().join("caches");
+    write(
+        &root.join("the-witcher-3-1650000000.yml"),
+        &format!(
+            "name: The Witcher 3\ngame:\n  exe: drive_c/GOG Games/The Witcher 3/bin/witcher3.exe\n  prefix: {}\n\
+             system:\n  env:\n    DXVK_STATE_CACHE_PATH: {}\n",
+            prefix.display(),
+            overridden.display()
+        ),
+    );
+    write(&root.join("broken.yml.bak"), "not: [yaml");
+    // A config that doesn't parse is skipped with a warning, and doesn't hide the other games
+    write(&root.join("broken-1650000001.yml"), "game: [exe");
+    write(&prefix.join("drive_c/GOG Games/The Witcher 3/bin/witcher3.dxvk-cache"), "");
+    write(&overridden.join("witcher3.dxvk-cache"), "");
+    let found = lutris::find_caches(&root).unwrap();
+    assert_eq!(found, vec![
+        cache(
+            Launcher::Lutris,
+            "the-witcher-3",
+            Some("The Witcher 3"),
+            prefix.join("drive_c/GOG Games/The Witcher 3/bin/witcher3.dxvk-cache"),
+        ),
+        cache(Launcher::Lutris, "the-witcher-3", Some("The Witcher 3"), overridden.join("witcher3.dxvk-cache")),
+    ]);
+}
+
+#[test]
+fn finds_heroic_caches() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("heroic");
+    let install = dir.path().join("Games/Heroic/Control");
+    let prefix = dir.path().join("Games/Heroic/Prefixes/Control");
+    write(
+        &root.join("legendaryConfig/legendary/installed.json"),
+        &format!(r#"{{"Calluna": {{"title": "Control", "install_path": "{}"}}}}"#, install.display()),
+    );
+    write(
+        &root.join("GamesConfig/Calluna.json"),
+        &format!(
+            r#"{{"Calluna": {{"winePrefix": "{}", "enviromentOptions": [{{"key": "DXVK_STATE_CACHE_PATH", "value": "C:\\dxvk"}}]}}}}"#,
+            prefix.display()
+        ),
+    );
+    write(&root.join("GamesConfig/Broken.json"), r#"{"Broken": {"winePrefix": "#);
+    write(&install.join("Control_DX12.dxvk-cache"), "");
+    write(&prefix.join("drive_c/dxvk/Control_DX11.dxvk-cache"), "");
+    let found = heroic::find_caches(&root).unwrap();
+    assert_eq!(found, vec![
+        cache(Launcher::Heroic, "Calluna", Some("Control"), install.join("Control_DX12.dxvk-cache")),
+        cache(
+            Launcher::Heroic,
+            "Calluna",
+            Some("Control"),
+            prefix.join("drive_c/dxvk/Control_DX11.dxvk-cache"),
+        ),
+    ]);
+}
+
+#[test]
+fn finds_bottles_caches() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("bottles");
+    let bottle = root.join("Gaming");
+    let game = bottle.join("drive_c/Program Files/Game");
+    write(
+        &bottle.join("bottle.yml"),
+        &format!(
+            "Name: Gaming\nExternal_Programs:\n  abc123:\n    name: Some Game\n    path: {}/game.exe\n",
+            game.display()
+        ),
+    );
+    write(&game.join("game.dxvk-cache"), "");
+    write(&bottle.join("drive_c/users/steamuser/other.dxvk-cache"), "");
+    write(&root.join("Broken/bottle.yml"), "Name: [Broken");
+    write(&root.join("Broken/drive_c/users/steamuser/broken.dxvk-cache"), "");
+    write(&root.join("library.yml"), "");
+    let found = bottles::find_caches(&root).unwrap();
+    assert_eq!(found, vec![
+        cache(Launcher::Bottles, "Gaming", None, bottle.join("drive_c/users/steamuser/other.dxvk-cache")),
+        cache(Launcher::Bottles, "Gaming", Some("Some Game"), game.join("game.dxvk-cache")),
+    ]);
+}
+
+#[test]
+fn locate_launcher_filter() {
+    let dir = tempfile::tempdir().unwrap();
+    let (root, _) = fake_steam(dir.path());
+    let lutris = dir.path().join("lutris");
+    let game = dir.path().join("game");
+    write(&lutris.join("game.yml"), &format!("game:\n  exe: {}/game.exe\n", game.display()));
+    write(&game.join("game.dxvk-cache"), "");
+    let locate = |args: &[&str]| {
+        let output = common::command()
+            .arg("locate")
+            .arg("--steam-root")
+            .arg(&root)
+            .arg("--lutris-root")
+            .arg(&lutris)
+            .args(args)
+            .env("HOME", dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(locate(&[]).lines().count(), 4);
+    assert_eq!(
+        locate(&["--launcher", "lutris"]),
+        format!("lutris\tgame\t-\t{}\n", game.join("game.dxvk-cache").display())
+    );
+}
+
+#[test]
+fn vdf_syntax() {
+    let doc = vdf::parse(
+        "// comment\n\"Root\"\n{\n\tkey \"quoted \\\"value\\\"\"\n\t\"Nested\" { \"a\" \"1\" }\n}\n",
+    ).unwrap();
+    let root = doc.get("root").unwrap();
+    assert_eq!(root.get_str("KEY"), Some("quoted \"value\""));
+    assert_eq!(root.get("nested").and_then(|v| v.get_str("a")), Some("1"));
+    assert!(vdf::parse("\"Root\" { \"a\" ").is_err());
+    assert!(vdf::parse("}").is_err());
+}
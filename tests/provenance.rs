@@ -0,0 +1,97 @@
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use common::tool;
+
+const SHARED: &str = "4748aec54dbbf7f1c14cafb03b251a3e7f7ca36f";
+const EXTRA: &str = "d87750f20da207e161e7cb3c2bb69a70789696c6";
+
+/// A fixture's path as passed on the command line, which is what provenance records
+fn fixture(name: &str) -> String {
+    common::fixture(name).to_str().unwrap().to_owned()
+}
+
+/// The sources and contributors `blame` reports, without the times
+fn blame(cache: &Path, hash: &str) -> Vec<(String, String)> {
+    let output = tool(&["blame", cache.to_str().unwrap(), hash]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+        .lines()
+        .map(|l| {
+            let fields: Vec<_> = l.split('\t').collect();
+            assert_eq!(fields.len(), 3);
+            (fields[0].to_owned(), fields[1].to_owned())
+        })
+        .collect()
+}
+
+fn record(source: &str, contributor: &str) -> (String, String) {
+    (source.to_owned(), contributor.to_owned())
+}
+
+#[test]
+fn merge_records_sources() {
+    let dir = tempfile::tempdir().unwrap();
+    let out: PathBuf = dir.path().join("out.dxvk-cache");
+    let output = tool(&[
+        "merge", "--provenance", "--contributor", "alice", "-o", out.to_str().unwrap(),
+        &fixture("v10.dxvk-cache"), &fixture("v10-extra.dxvk-cache"),
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(fs::read_to_string(dir.path().join("out.dxvk-cache.provenance")).unwrap().lines().count(), 10);
+    assert_eq!(blame(&out, SHARED), [
+        record(&fixture("v10.dxvk-cache"), "alice"),
+        record(&fixture("v10-extra.dxvk-cache"), "alice"),
+    ]);
+    assert_eq!(blame(&out, &EXTRA[..8]), [record(&fixture("v10-extra.dxvk-cache"), "alice")]);
+
+    // Merging the merged cache again keeps the original sources
+    let master = dir.path().join("master.dxvk-cache");
+    let output = tool(&[
+        "merge", "--provenance", "--contributor", "bob", "-o", master.to_str().unwrap(),
+        out.to_str().unwrap(), &fixture("v10.dxvk-cache"),
+    ]);
+    assert!(output.status.success());
+    assert_eq!(blame(&master, EXTRA), [record(&fixture("v10-extra.dxvk-cache"), "alice")]);
+    assert_eq!(blame(&master, SHARED), [
+        record(&fixture("v10.dxvk-cache"), "alice"),
+        record(&fixture("v10-extra.dxvk-cache"), "alice"),
+        record(&fixture("v10.dxvk-cache"), "bob"),
+    ]);
+}
+
+#[test]
+fn append_extends_provenance() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out.dxvk-cache");
+    let merge = |input: &str, contributor: &str| {
+        let output = tool(&[
+            "merge", "--append", "--provenance", "--contributor", contributor, "-o", out.to_str().unwrap(), input,
+        ]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    };
+    merge(&fixture("v10.dxvk-cache"), "alice");
+    merge(&fixture("v10-extra.dxvk-cache"), "bob");
+    assert_eq!(blame(&out, EXTRA), [record(&fixture("v10-extra.dxvk-cache"), "bob")]);
+    assert_eq!(blame(&out, SHARED).len(), 2);
+}
+
+#[test]
+fn blame_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out.dxvk-cache");
+    let output = tool(&["merge", "-o", out.to_str().unwrap(), &fixture("v10-extra.dxvk-cache")]);
+    assert!(output.status.success());
+    let out = out.to_str().unwrap();
+
+    let output = tool(&["blame", out, SHARED]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has no provenance"));
+    let output = tool(&["blame", out, "0000"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No entry 0000"));
+    let output = tool(&["blame", out, "d"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("matches several entries"));
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use dxvk_cache_tool::resolve::{
+    self,
+    Disabled,
+    Environment,
+};
+use common::write;
+
+fn env(vars: &[(&str, &str)]) -> Environment {
+    vars.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+}
+
+/// A game installed in a Wine prefix, returning the executable's path
+fn fake_game(dir: &Path) -> PathBuf {
+    let exe = dir.join("pfx/drive_c/Games/Game/Game.exe");
+    write(&exe, "");
+    exe
+}
+
+#[test]
+fn next_to_executable() {
+    let dir = tempfile::tempdir().unwrap();
+    let exe = fake_game(dir.path());
+    let resolution = resolve::resolve(&exe, &env(&[]), None).unwrap();
+    assert_eq!(resolution.path, exe.with_file_name("Game.dxvk-cache"));
+    assert_eq!(resolution.config, None);
+    assert!(resolution.enabled());
+}
+
+#[test]
+fn state_cache_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let exe = fake_game(dir.path());
+    let pfx = dir.path().join("pfx");
+    let resolve = |value: &str| {
+        resolve::resolve(&exe, &env(&[("DXVK_STATE_CACHE_PATH", value)]), None).map(|r| r.path)
+    };
+    assert_eq!(resolve("/tmp/caches").unwrap(), Path::new("/tmp/caches/Game.dxvk-cache"));
+    assert_eq!(resolve("C:\\caches").unwrap(), pfx.join("drive_c/caches/Game.dxvk-cache"));
+    assert_eq!(resolve("caches").unwrap(), exe.with_file_name("caches/Game.dxvk-cache"));
+    let outside = dir.path().join("Game.exe");
+    assert!(resolve::resolve(&outside, &env(&[("DXVK_STATE_CACHE_PATH", "C:\\caches")]), None).is_err());
+}
+
+#[test]
+fn disabled() {
+    let dir = tempfile::tempdir().unwrap();
+    let exe = fake_game(dir.path());
+    let conf = exe.with_file_name("dxvk.conf");
+    let disabled = |vars: &[(&str, &str)]| resolve::resolve(&exe, &env(vars), None).unwrap().disabled;
+
+    assert_eq!(disabled(&[("DXVK_STATE_CACHE", "0")]), Some(Disabled::Environment));
+    assert_eq!(disabled(&[("DXVK_STATE_CACHE", "1")]), None);
+
+    write(&conf, "# comment\ndxvk.enableStateCache = False\n");
+    assert_eq!(disabled(&[]), Some(Disabled::Config(Some(conf.clone()))));
+    assert_eq!(disabled(&[("DXVK_CONFIG", "dxvk.enableStateCache = True")]), None);
+
+    // Sections only apply to the executable they name
+    write(&conf, "[Other.exe]\ndxvk.enableStateCache = False\n[Game.exe]\ndxvk.enableStateCache = True\n");
+    assert_eq!(disabled(&[]), None);
+    write(&conf, "[Game.exe]\ndxvk.enableStateCache = \"False\"\n");
+    assert_eq!(disabled(&[]), Some(Disabled::Config(Some(conf.clone()))));
+
+    let other = dir.path().join("other.conf");
+    write(&other, "dxvk.enableStateCache = True\n");
+    assert_eq!(disabled(&[("DXVK_CONFIG_FILE", other.to_str().unwrap())]), None);
+    let resolution = resolve::resolve(&exe, &env(&[]), Some(&other)).unwrap();
+    assert_eq!(resolution.config, Some(other));
+}
+
+#[test]
+fn env_file() {
+    let vars = resolve::parse_env_file(
+        "# launcher environment\n\nexport DXVK_STATE_CACHE=0\nDXVK_STATE_CACHE_PATH=\"/tmp/a b\"\nnot a variable\n",
+    );
+    assert_eq!(vars, env(&[("DXVK_STATE_CACHE", "0"), ("DXVK_STATE_CACHE_PATH", "/tmp/a b")]));
+}
+
+#[test]
+fn where_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let exe = fake_game(dir.path());
+    let env_file = dir.path().join("env");
+    write(&env_file, "DXVK_STATE_CACHE=0\n");
+    write(&exe.with_file_name("Game.dxvk-cache"), "");
+    let output = common::command()
+        .arg("where")
+        .arg(&exe)
+        .arg("--env-file")
+        .arg(&env_file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "path: {}\nexists: yes\nconfig: none\nenabled: no (DXVK_STATE_CACHE=0)\n",
+            exe.with_file_name("Game.dxvk-cache").display()
+        )
+    );
+}
@@ -0,0 +1,252 @@
+use std::{
+    collections::HashSet,
+    num::NonZeroU32,
+};
+use dxvk_cache_tool::dxvk::*;
+use proptest::{
+    collection::vec,
+    prelude::*,
+};
+
+fn make_cache(header: DxvkStateCacheHeader, entries: &[DxvkStateCacheEntry]) -> DxvkStateCache {
+    DxvkStateCache {
+        header: header,
+        entries: entries.iter().cloned().map(EntryWrapper::from).collect(),
+    }
+}
+
+/// Entries sorted by hash, so caches can be compared including each entry's full contents
+fn sorted_entries(cache: &DxvkStateCache) -> Vec<DxvkStateCacheEntry> {
+    let mut entries: Vec<_> = cache.iter().cloned().collect();
+    entries.sort_by_key(|e| e.hash);
+    entries
+}
+
+fn hashes(cache: &DxvkStateCache) -> HashSet<Sha1Hash> {
+    cache.iter().map(|e| e.hash).collect()
+}
+
+fn standard_cache() -> impl Strategy<Value = DxvkStateCache> {
+    let version = (LEGACY_VERSION + 1)..=32;
+    let entries = vec((any::<u8>(), vec(any::<u8>(), 0..128)), 1..32);
+    (version, entries).prop_map(|(version, entries)| {
+        let header = DxvkStateCacheHeader::new(NonZeroU32::new(version).unwrap(), 0);
+        let entries: Vec<_> = entries.into_iter()
+            .map(|(mask, data)| DxvkStateCacheEntry::new(data, mask, DxvkStateCacheEdition::Standard))
+            .collect();
+        make_cache(header, &entries)
+    })
+}
+
+fn legacy_cache() -> impl Strategy<Value = DxvkStateCache> {
+    (1..=LEGACY_VERSION, 1usize..128).prop_flat_map(|(version, data_size)| {
+        vec(vec(any::<u8>(), data_size), 1..32).prop_map(move |entries| {
+            let entry_size = (data_size + HASH_SIZE) as u32;
+            let header = DxvkStateCacheHeader::new(NonZeroU32::new(version).unwrap(), entry_size);
+            let entries: Vec<_> = entries.into_iter()
+                .map(|data| DxvkStateCacheEntry::new(data, 0, DxvkStateCacheEdition::Legacy))
+                .collect();
+            make_cache(header, &entries)
+        })
+    })
+}
+
+/// Three caches sharing a header, drawn from a common pool of entries so they overlap
+fn overlapping_caches() -> impl Strategy<Value = (DxvkStateCache, DxvkStateCache, DxvkStateCache)> {
+    let pool = vec((any::<u8>(), vec(any::<u8>(), 0..32)), 1..24);
+    pool.prop_flat_map(|pool| {
+        let len = pool.len();
+        let pick = move || vec(any::<bool>(), len);
+        (Just(pool), pick(), pick(), pick())
+    }).prop_map(|(pool, a, b, c)| {
+        let header = DxvkStateCacheHeader::new(NonZeroU32::new(LEGACY_VERSION + 1).unwrap(), 0);
+        let pool: Vec<_> = pool.into_iter()
+            .map(|(mask, data)| DxvkStateCacheEntry::new(data, mask, DxvkStateCacheEdition::Standard))
+            .collect();
+        let subset = |picks: Vec<bool>| {
+            let entries: Vec<_> = pool.iter()
+                .zip(picks)
+                .filter(|&(_, picked)| picked)
+                .map(|(e, _)| e.clone())
+                .collect();
+            make_cache(header, &entries)
+        };
+        (subset(a), subset(b), subset(c))
+    })
+}
+
+fn assert_round_trip(cache: &DxvkStateCache) -> Result<(), TestCaseError> {
+    let mut buf = Vec::new();
+    cache.write_to(&mut buf).unwrap();
+    let read = DxvkStateCache::from_reader_with(&buf[..], &ReadOptions::default()).unwrap();
+    prop_assert_eq!(read.header.version, cache.header.version);
+    prop_assert_eq!(read.header.entry_size, cache.header.entry_size);
+    prop_assert_eq!(sorted_entries(&read), sorted_entries(cache));
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn standard_round_trip(cache in standard_cache()) {
+        assert_round_trip(&cache)?;
+    }
+
+    #[test]
+    fn legacy_round_trip(cache in legacy_cache()) {
+        assert_round_trip(&cache)?;
+    }
+
+    #[test]
+    fn merge_laws((a, b, c) in overlapping_caches()) {
+        let ab = a.merge(&b).unwrap();
+        prop_assert_eq!(hashes(&ab), hashes(&b.merge(&a).unwrap()));
+        prop_assert_eq!(
+            hashes(&ab.merge(&c).unwrap()),
+            hashes(&a.merge(&b.merge(&c).unwrap()).unwrap())
+        );
+        prop_assert_eq!(hashes(&a.merge(&a).unwrap()), hashes(&a));
+        prop_assert_eq!(hashes(&ab), hashes(&a).union(&hashes(&b)).copied().collect());
+    }
+
+    #[test]
+    fn intersect_laws((a, b, c) in overlapping_caches()) {
+        let ab = a.intersect(&b).unwrap();
+        prop_assert_eq!(hashes(&ab), hashes(&b.intersect(&a).unwrap()));
+        prop_assert_eq!(
+            hashes(&ab.intersect(&c).unwrap()),
+            hashes(&a.intersect(&b.intersect(&c).unwrap()).unwrap())
+        );
+        prop_assert_eq!(hashes(&a.intersect(&a).unwrap()), hashes(&a));
+        prop_assert_eq!(hashes(&a.merge(&ab).unwrap()), hashes(&a));
+        prop_assert_eq!(hashes(&a.intersect(&a.merge(&b).unwrap()).unwrap()), hashes(&a));
+        prop_assert_eq!(
+            hashes(&a.intersect(&b.merge(&c).unwrap()).unwrap()),
+            hashes(&ab.merge(&a.intersect(&c).unwrap()).unwrap())
+        );
+    }
+
+    #[test]
+    fn difference_laws((a, b, _) in overlapping_caches()) {
+        let diff = a.difference(&b).unwrap();
+        prop_assert!(a.difference(&a).unwrap().entries.is_empty());
+        prop_assert!(diff.intersect(&b).unwrap().entries.is_empty());
+        prop_assert_eq!(
+            hashes(&diff.merge(&a.intersect(&b).unwrap()).unwrap()),
+            hashes(&a)
+        );
+        prop_assert_eq!(
+            hashes(&diff),
+            hashes(&a).difference(&hashes(&b)).copied().collect()
+        );
+    }
+}
+
+#[test]
+fn set_operations_check_version() {
+    let v8 = DxvkStateCacheHeader::new(NonZeroU32::new(8).unwrap(), 0);
+    let v9 = DxvkStateCacheHeader::new(NonZeroU32::new(9).unwrap(), 0);
+    let entry = DxvkStateCacheEntry::new(vec![1, 2, 3], 0x11, DxvkStateCacheEdition::Standard);
+    let a = make_cache(v8, std::slice::from_ref(&entry));
+    let b = make_cache(v9, &[entry]);
+    assert!(matches!(a.merge(&b), Err(ReadError::VersionMismatch { .. })));
+    assert!(matches!(a.intersect(&b), Err(ReadError::VersionMismatch { .. })));
+    assert!(matches!(a.difference(&b), Err(ReadError::VersionMismatch { .. })));
+
+    // Legacy entries have the header's fixed size, so it has to match as well
+    let small = DxvkStateCacheHeader::new(NonZeroU32::new(5).unwrap(), 24);
+    let large = DxvkStateCacheHeader::new(NonZeroU32::new(5).unwrap(), 25);
+    let a = make_cache(small, &[DxvkStateCacheEntry::new(vec![1; 4], 0, DxvkStateCacheEdition::Legacy)]);
+    let b = make_cache(large, &[DxvkStateCacheEntry::new(vec![2; 5], 0, DxvkStateCacheEdition::Legacy)]);
+    for res in [a.merge(&b), a.intersect(&b), a.difference(&b)] {
+        assert!(matches!(res, Err(ReadError::EntrySizeMismatch { found: 25, expected: 24 })));
+    }
+    let mut data = Vec::new();
+    b.write_to(&mut data).unwrap();
+    let mut a = a;
+    assert!(matches!(a.append_from(&data[..], &ReadOptions::default()), Err(ReadError::EntrySizeMismatch { .. })));
+}
+
+#[test]
+fn errors_carry_entry_position() {
+    let header = DxvkStateCacheHeader::new(NonZeroU32::new(10).unwrap(), 0);
+    let entries: Vec<_> = [&[1u8, 2, 3][..], &[4, 5, 6, 7, 8], &[9]].iter()
+        .map(|data| DxvkStateCacheEntry::new(data.to_vec(), 0x11, DxvkStateCacheEdition::Standard))
+        .collect();
+    let write = |entries: &[&DxvkStateCacheEntry]| {
+        let mut data = Vec::new();
+        header.write_to(&mut data).unwrap();
+        for e in entries {
+            e.write_to(&mut data, DxvkStateCacheEdition::Standard).unwrap();
+        }
+        data
+    };
+
+    // Each standard entry takes a 4 byte header and a 20 byte hash besides its data
+    let mut data = write(&[&entries[0], &entries[1], &entries[2]]);
+    *data.last_mut().unwrap() ^= 0xff;
+    let e = DxvkStateCache::from_reader_with(&data[..], &ReadOptions::default()).unwrap_err();
+    let position = EntryPosition {
+        index: 2,
+        offset: 12 + 27 + 29,
+    };
+    assert!(matches!(e, ReadError::ReadEntry(p, EntryError::HashMismatch) if p == position));
+    assert_eq!(e.in_file("foo.dxvk-cache").to_string(), "foo.dxvk-cache: entry #2 @0x44: hash mismatch");
+
+    let data = write(&[&entries[0], &entries[1], &entries[0]]);
+    let e = DxvkStateCache::from_reader_with(&data[..], &ReadOptions::default()).unwrap_err();
+    assert!(matches!(e, ReadError::DuplicateEntry(p) if p == position));
+    let e = e.in_file("foo.dxvk-cache").in_file("bar.dxvk-cache");
+    assert_eq!(e.path(), Some(std::path::Path::new("foo.dxvk-cache")));
+    assert!(matches!(e.inner(), ReadError::DuplicateEntry(..)));
+}
+
+#[test]
+fn stage_masks_and_hash_prefixes() {
+    assert_eq!(StageMaskDisplay(0x11).to_string(), "VS|FS");
+    assert_eq!(StageMaskDisplay(0x3f).to_string(), "VS|TCS|TES|GS|FS|CS");
+    assert_eq!(StageMaskDisplay(0xa0).to_string(), "CS|0x80");
+    assert_eq!(StageMaskDisplay(0).to_string(), "none");
+
+    let hash = parse_hash("4748aec54dbbf7f1c14cafb03b251a3e7f7ca36f").unwrap();
+    assert!("4748AE".parse::<HashPrefix>().unwrap().matches(&hash));
+    assert!("4748aec54dbbf7f1c14cafb03b251a3e7f7ca36f".parse::<HashPrefix>().unwrap().matches(&hash));
+    assert!(!"4749".parse::<HashPrefix>().unwrap().matches(&hash));
+    for s in ["", "47g8", "4748aec54dbbf7f1c14cafb03b251a3e7f7ca36f0"] {
+        assert!(s.parse::<HashPrefix>().is_err());
+    }
+}
+
+#[test]
+fn limits_are_enforced() {
+    let header = DxvkStateCacheHeader::new(NonZeroU32::new(10).unwrap(), 0);
+    let entry = DxvkStateCacheEntry::new(vec![7; 64], 0x11, DxvkStateCacheEdition::Standard);
+    let limits = ReadLimits {
+        max_entry_size: 63,
+        ..ReadLimits::default()
+    };
+    let mut data = Vec::new();
+    entry.write_to(&mut data, DxvkStateCacheEdition::Standard).unwrap();
+    let e = DxvkStateCacheEntry::from_reader(&data[..], &header, &limits).unwrap_err();
+    assert!(matches!(e, EntryError::TooLarge { size: 64, limit: 63 }));
+    let limits = ReadLimits {
+        max_entry_size: 64,
+        ..ReadLimits::default()
+    };
+    assert!(DxvkStateCacheEntry::from_reader(&data[..], &header, &limits).is_ok());
+
+    // The total counts every entry read so far, not just the last one
+    let limits = ReadLimits {
+        max_cache_size: 127,
+        ..ReadLimits::default()
+    };
+    let other = DxvkStateCacheEntry::new(vec![8; 64], 0x11, DxvkStateCacheEdition::Standard);
+    other.write_to(&mut data, DxvkStateCacheEdition::Standard).unwrap();
+    let mut reader = EntryReader::new(&data[..], header, limits);
+    assert!(reader.next_entry().unwrap().is_some());
+    assert!(matches!(reader.next_entry(), Err(EntryError::CacheTooLarge(127))));
+
+    // Headers normally keep legacy entry sizes in bounds, but entries can be read on their own
+    let header = DxvkStateCacheHeader::new(NonZeroU32::new(LEGACY_VERSION).unwrap(), HASH_SIZE as u32 - 1);
+    let e = DxvkStateCacheEntry::from_reader(&[0; 64][..], &header, &ReadLimits::default()).unwrap_err();
+    assert!(matches!(e, EntryError::TooSmall(19)));
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+use common::fixture;
+
+fn store(root: &Path, args: &[&str]) -> Command {
+    let mut command = common::command();
+    command
+        .arg("store")
+        .arg("--store")
+        .arg(root)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    command
+}
+
+/// Takes the store's lock the way another process changing it would
+fn hold_lock(root: &Path) -> fs::File {
+    let lock = fs::File::options().create(true).truncate(false).write(true).open(root.join("lock")).unwrap();
+    lock.lock().unwrap();
+    lock
+}
+
+#[test]
+fn changes_wait_for_the_lock() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("store");
+    assert!(store(&root, &["list"]).status().unwrap().success());
+
+    let lock = hold_lock(&root);
+    let mut add = store(&root, &["add", "--game", "game"])
+        .arg(fixture("v10.dxvk-cache"))
+        .spawn()
+        .unwrap();
+    // Reading doesn't need the lock
+    assert!(store(&root, &["list"]).status().unwrap().success());
+    thread::sleep(Duration::from_millis(500));
+    assert!(add.try_wait().unwrap().is_none());
+    assert!(!root.join("manifests/game").exists());
+
+    drop(lock);
+    assert!(add.wait().unwrap().success());
+    assert!(root.join("manifests/game").exists());
+}
+
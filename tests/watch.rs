@@ -0,0 +1,74 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{
+    fs,
+    path::Path,
+    process::Child,
+    thread,
+    time::{Duration, Instant},
+};
+use dxvk_cache_tool::dxvk::{DxvkStateCache, ReadOptions};
+use common::fixture;
+
+/// Kills the watcher when the test ends, whether it passed or not
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn entries(path: &Path) -> Option<usize> {
+    DxvkStateCache::from_file(path, &ReadOptions::default())
+        .ok()
+        .map(|cache| cache.entries.len())
+}
+
+/// Waits for the master cache to hold `count` entries
+fn wait_for(path: &Path, count: usize) {
+    let start = Instant::now();
+    while entries(path) != Some(count) {
+        assert!(start.elapsed() < Duration::from_secs(15), "{} never got {} entries", path.display(), count);
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn merges_idle_caches() {
+    let dir = tempfile::tempdir().unwrap();
+    let games = dir.path().join("games");
+    let master = dir.path().join("master.dxvk-cache");
+    fs::create_dir(&games).unwrap();
+    let _watcher = KillOnDrop(
+        common::command()
+            .arg("watch")
+            .arg(&games)
+            .args(["--idle", "1", "-o"])
+            .arg(&master)
+            .spawn()
+            .unwrap()
+    );
+    // Give it time to set up its watches
+    thread::sleep(Duration::from_secs(1));
+
+    fs::copy(fixture("v10.dxvk-cache"), games.join("game.dxvk-cache")).unwrap();
+    wait_for(&master, 4);
+    // An entry that doesn't match its hash is left out, and the rest still get merged
+    let mut damaged = fs::read(fixture("v10-extra.dxvk-cache")).unwrap();
+    *damaged.last_mut().unwrap() ^= 0xff;
+    fs::write(games.join("game.dxvk-cache"), damaged).unwrap();
+    wait_for(&master, 5);
+    // The game ran again and compiled more pipelines
+    fs::copy(fixture("v10-extra.dxvk-cache"), games.join("game.dxvk-cache")).unwrap();
+    wait_for(&master, 6);
+    // Other versions can't be merged into the master, and don't stop the watcher
+    fs::copy(fixture("v11.dxvk-cache"), games.join("other.dxvk-cache")).unwrap();
+    fs::write(games.join("notes.txt"), "").unwrap();
+    fs::copy(fixture("v10.dxvk-cache"), games.join("again.dxvk-cache")).unwrap();
+    thread::sleep(Duration::from_secs(3));
+    assert_eq!(entries(&master), Some(6));
+}